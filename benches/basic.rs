@@ -1,5 +1,5 @@
 use criterion::{Criterion, criterion_group, criterion_main};
-use glass_rs::Glass;
+use glass_rs::{Glass, GlassU32};
 use rand::distr::Uniform;
 use rand::prelude::*;
 use rand::rng;
@@ -44,6 +44,30 @@ fn bench_insert(c: &mut Criterion) {
     });
 }
 
+fn bench_insert_many(c: &mut Criterion) {
+    let keys = generate_random_keys(N);
+    let values = generate_random_values(N);
+    let batch: Vec<(u32, u64)> = keys.iter().copied().zip(values.iter().copied()).collect();
+
+    c.bench_function("insert_many_unsorted", |b| {
+        b.iter(|| {
+            let mut glass = Glass::new();
+            glass.insert_many_unsorted(black_box(&batch));
+            black_box(glass.len())
+        })
+    });
+
+    c.bench_function("insert_many_unsorted_loop", |b| {
+        b.iter(|| {
+            let mut glass = Glass::new();
+            for &(k, v) in black_box(&batch) {
+                glass.insert(k, v);
+            }
+            black_box(glass.len())
+        })
+    });
+}
+
 fn bench_get(c: &mut Criterion) {
     let keys = generate_random_keys(N);
     let values = generate_random_values(N);
@@ -98,6 +122,15 @@ fn bench_get(c: &mut Criterion) {
             }
         })
     });
+
+    let frozen = glass.freeze();
+    c.bench_function("get_existing_frozen", |b| {
+        b.iter(|| {
+            for &key in &keys {
+                black_box(frozen.get(key));
+            }
+        })
+    });
 }
 
 fn bench_remove(c: &mut Criterion) {
@@ -139,6 +172,9 @@ fn bench_min_max(c: &mut Criterion) {
 
     c.bench_function("min", |b| b.iter(|| black_box(glass.min())));
     c.bench_function("max", |b| b.iter(|| black_box(glass.max())));
+    c.bench_function("best_price_volume", |b| {
+        b.iter(|| black_box(glass.best_price_volume()))
+    });
 
     let mut map = BTreeMap::new();
     for i in 0..N {
@@ -338,6 +374,10 @@ fn bench_deep_sweep(c: &mut Criterion) {
     c.bench_function("compute_buy_cost_deep", |b| {
         b.iter(|| black_box(glass.compute_buy_cost(black_box(deep_target))))
     });
+    // Target beyond the whole book: answered without the ordered sweep.
+    c.bench_function("compute_buy_cost_whole_book", |b| {
+        b.iter(|| black_box(glass.compute_buy_cost(black_box(u64::MAX))))
+    });
 
     let mut map = BTreeMap::new();
     for i in 0..N {
@@ -415,6 +455,147 @@ fn bench_deep_sweep(c: &mut Criterion) {
 }
 
 /// Benchmarks the `remove_by_index` function under different scenarios.
+// Remove/re-insert churn on a book just past the 4096-level trie capacity.
+// With eager refill every remove pulls a level in from the overflow tier and
+// every insert evicts one back out; a refill slack absorbs the churn.
+fn bench_capacity_edge(c: &mut Criterion) {
+    for (name, slack) in [
+        ("capacity_edge_churn", 1),
+        ("capacity_edge_churn_slack64", 64),
+    ] {
+        let mut glass = Glass::new().with_refill_slack(slack);
+        for i in 0..5000u32 {
+            glass.insert(i * 2, 1);
+        }
+        c.bench_function(name, |b| {
+            b.iter(|| {
+                for i in 0..32u32 {
+                    let key = black_box(i * 2);
+                    glass.remove(key);
+                    glass.insert(key, 1);
+                }
+            })
+        });
+    }
+}
+
+// Cancelling 256 trie levels of a spilled book, then restoring them: one
+// refill per removal vs one per batch.
+fn bench_bulk_cancel(c: &mut Criterion) {
+    let keys: Vec<u32> = (0..256u32).map(|i| i * 8).collect();
+    let mut glass = Glass::new();
+    for i in 0..6000u32 {
+        glass.insert(i * 2, 1);
+    }
+    c.bench_function("bulk_cancel_remove_loop", |b| {
+        b.iter(|| {
+            for &key in &keys {
+                glass.remove(black_box(key));
+            }
+            for &key in &keys {
+                glass.insert(key, 1);
+            }
+        })
+    });
+    c.bench_function("bulk_cancel_remove_all", |b| {
+        b.iter(|| {
+            black_box(glass.remove_all(black_box(&keys)));
+            for &key in &keys {
+                glass.insert(key, 1);
+            }
+        })
+    });
+}
+
+// Sorted lookups (e.g. reconciling a sorted order list): per-key get vs
+// the leaf-cursor batch.
+fn bench_bulk_get_sorted(c: &mut Criterion) {
+    let mut glass = Glass::new();
+    for i in 0..3000u32 {
+        glass.insert(i * 2, 1);
+    }
+    let mut keys = generate_random_keys(4096);
+    keys.sort_unstable();
+    let mut out = Vec::with_capacity(keys.len());
+    c.bench_function("sorted_get_loop", |b| {
+        b.iter(|| {
+            for &key in &keys {
+                black_box(glass.get(key));
+            }
+        })
+    });
+    c.bench_function("bulk_get_sorted", |b| {
+        b.iter(|| {
+            glass.bulk_get_sorted(black_box(&keys), &mut out);
+            black_box(&out);
+        })
+    });
+}
+
+// Per-tick snapshot reload of a 2000-level book.
+fn bench_replace_contents(c: &mut Criterion) {
+    let snapshot: Vec<(u32, u64)> = (0..2000u32).map(|i| (i * 3, 1 + (i % 7) as u64)).collect();
+    let mut glass = Glass::new();
+    c.bench_function("reload_clear_extend", |b| {
+        b.iter(|| {
+            glass.clear();
+            glass.extend(black_box(&snapshot).iter().copied());
+        })
+    });
+    c.bench_function("reload_replace_contents", |b| {
+        b.iter(|| glass.replace_contents(black_box(&snapshot).iter().copied()))
+    });
+}
+
+// Volume sums over a full, dense trie (64 leaves) and a sparse one (a level
+// or two per leaf). total_volume is maintained, so a whole-book range sum is
+// what exercises the per-leaf kernels; the per-level fold is the scalar
+// reference.
+fn bench_volume_sums(c: &mut Criterion) {
+    let dense: Glass = (0..4096u32).map(|i| (i, 1 + (i % 13) as u64)).collect();
+    let sparse: Glass = (0..4096u32)
+        .map(|i| (i * 29, 1 + (i % 13) as u64))
+        .collect();
+    let compact: GlassU32 = (0..4096u32).map(|i| (i, 1 + i % 13)).collect();
+    c.bench_function("volume_sum_dense", |b| {
+        b.iter(|| black_box(&dense).sum_values_in_range(0, u32::MAX))
+    });
+    c.bench_function("volume_sum_sparse", |b| {
+        b.iter(|| black_box(&sparse).sum_values_in_range(0, u32::MAX))
+    });
+    c.bench_function("volume_sum_dense_u32", |b| {
+        b.iter(|| black_box(&compact).sum_values_in_range(0, u32::MAX))
+    });
+    c.bench_function("volume_sum_dense_fold", |b| {
+        b.iter(|| black_box(&dense).iter().map(|(_, v)| v).sum::<u64>())
+    });
+}
+
+// Same books as bench_get / bench_deep_sweep with u32 quantities: half the
+// leaf value footprint.
+fn bench_compact(c: &mut Criterion) {
+    let keys = generate_random_keys(N);
+    let values = generate_random_values(N);
+    let mut glass = GlassU32::new_compact();
+    for i in 0..N {
+        glass.insert(keys[i], values[i] as u32);
+    }
+
+    c.bench_function("get_existing_u32", |b| {
+        b.iter(|| {
+            for &key in &keys {
+                black_box(glass.get(key));
+            }
+        })
+    });
+    c.bench_function("compute_buy_cost_u32", |b| {
+        b.iter(|| black_box(glass.compute_buy_cost(black_box(1000))))
+    });
+    c.bench_function("compute_buy_cost_deep_u32", |b| {
+        b.iter(|| black_box(glass.compute_buy_cost(black_box(500_000))))
+    });
+}
+
 fn bench_remove_by_index(c: &mut Criterion) {
     let keys = generate_random_keys(N);
     let values = generate_random_values(N);
@@ -634,9 +815,9 @@ fn bench_remove_by_index_btree(c: &mut Criterion) {
 criterion_group! {
     name = benches;
     config = Criterion::default().measurement_time(Duration::from_secs(6));
-    targets = bench_insert, bench_get, bench_remove, bench_min_max, bench_compute_buy_cost,
-        bench_buy_shares, bench_sell, bench_top_levels, bench_deep_sweep,
-        bench_remove_by_index, bench_remove_by_index_btree
+    targets = bench_insert, bench_insert_many, bench_get, bench_remove, bench_min_max, bench_compute_buy_cost,
+        bench_buy_shares, bench_sell, bench_top_levels, bench_deep_sweep, bench_compact,
+        bench_capacity_edge, bench_bulk_cancel, bench_bulk_get_sorted, bench_replace_contents, bench_volume_sums, bench_remove_by_index, bench_remove_by_index_btree
 }
 
 criterion_main!(benches);
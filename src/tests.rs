@@ -1,6 +1,8 @@
 #[cfg(test)]
 mod tests {
     use super::*;
+    use proptest::prelude::*;
+    use std::collections::BTreeMap;
 
     #[test]
     fn test_new() {
@@ -107,6 +109,636 @@ mod tests {
         assert_eq!(glass.glass_size(), 4096);
     }
 
+    #[test]
+    fn test_refill_slack() {
+        let mut glass = Glass::new().with_refill_slack(8);
+        for i in 0..(4096 + 100) {
+            glass.insert(i as u32, 1);
+        }
+        // Fewer than 8 free slots: the overflow tier is left alone.
+        for i in 0..7 {
+            glass.remove(i);
+        }
+        assert_eq!(glass.glass_size(), 4096 - 7);
+        assert_eq!(unsafe { &*glass.preempt.get() }.len(), 100);
+        assert_eq!(glass.min(), Some((7, 1)));
+        assert_eq!(glass.get(4096 + 50), Some(1));
+        // The 8th free slot triggers one refill back to capacity.
+        glass.remove(7);
+        assert_eq!(glass.glass_size(), 4096);
+        assert_eq!(unsafe { &*glass.preempt.get() }.len(), 92);
+        assert_eq!(glass.len(), 4096 + 92);
+
+        // Slack is clamped: 0 behaves as 1, and a fully drained trie always
+        // refills even with the maximum slack.
+        let mut eager = Glass::new().with_refill_slack(0);
+        assert_eq!(eager.refill_slack, 1);
+        let mut lazy = Glass::new().with_refill_slack(usize::MAX);
+        assert_eq!(lazy.refill_slack, MAX_SIZE);
+        for i in 0..(4096 + 10) {
+            eager.insert(i as u32, 1);
+            lazy.insert(i as u32, 1);
+        }
+        eager.remove(0);
+        assert_eq!(eager.glass_size(), 4096);
+        lazy.remove(0);
+        assert_eq!(lazy.glass_size(), 4095);
+        assert_eq!(lazy.buy_shares(4095), 4095 * 4096 / 2);
+        assert_eq!(lazy.min(), Some((4096, 1)));
+        assert_eq!(lazy.glass_size(), 10);
+    }
+
+    #[test]
+    fn test_rebalance() {
+        // Refill path: trie left short by refill slack.
+        let mut glass = Glass::new().with_refill_slack(64);
+        let mut reference = BTreeMap::new();
+        for i in 0..5000u32 {
+            glass.insert(i, 1);
+            reference.insert(i, 1);
+        }
+        for i in 0..10u32 {
+            glass.remove(i);
+            reference.remove(&i);
+        }
+        assert_eq!(glass.glass_size(), 4086);
+        glass.rebalance();
+        assert_eq!(glass.glass_size(), 4096);
+        glass.assert_equivalent(&reference);
+
+        // Rebuild path: small keys planted straight into the overflow tier, as
+        // a stale routing check would, so the split no longer holds.
+        let mut glass = Glass::new();
+        let mut reference = BTreeMap::new();
+        for i in 0..4096u32 {
+            glass.insert(i * 2, 2);
+            reference.insert(i * 2, 2);
+        }
+        for k in [1u32, 3, 9001, u32::MAX] {
+            glass.preempt_insert(k, 7);
+            reference.insert(k, 7);
+        }
+        glass.ensure_sorted_preempt_keys();
+        glass.rebalance();
+        assert_eq!(glass.glass_size(), 4096);
+        assert_eq!(glass.glass_get(1), Some(7));
+        assert_eq!(glass.glass_get(3), Some(7));
+        assert_eq!(unsafe { &*glass.preempt.get() }.len(), 4);
+        assert_eq!(glass.thres.get(), 8188);
+        glass.assert_equivalent(&reference);
+    }
+
+    #[test]
+    fn test_index_paths_survive_corrupt_counts() {
+        // Inflate, deflate and zero internal-node counts (as a count-maintenance
+        // bug would): index queries must answer None or a present key, not
+        // panic or loop.
+        for corruption in [|c: u32| c + 7, |c: u32| c / 2, |_| 0, |_| u32::MAX / 2] {
+            let mut glass = Glass::new();
+            for i in 0..3000u32 {
+                glass.insert(i * 5, 1);
+            }
+            for i in (1..glass.arena.len()).step_by(3) {
+                glass.arena[i].count = corruption(glass.arena[i].count);
+            }
+            let len = glass.len();
+            for k in (0..len + 50).step_by(13) {
+                if let Some((key, v)) = glass.nth(k) {
+                    assert_eq!(glass.get(key), Some(v));
+                }
+            }
+            assert_eq!(glass.nth(usize::MAX), None);
+            for key in (0..15_100).step_by(97) {
+                let _ = glass.rank(key);
+                let _ = glass.get_with_rank(key);
+                let _ = glass.count_in_range(key, key + 500);
+            }
+            let _ = glass.remove_by_index(len / 2);
+        }
+    }
+
+    #[test]
+    fn test_replace_contents_reuses_capacity() {
+        let mut glass = Glass::new();
+        for i in 0..6000u32 {
+            glass.insert(i * 3, 1);
+        }
+        let caps = |g: &Glass| {
+            (g.arena.capacity(), g.leaf_arena.capacity(), unsafe {
+                (*g.preempt.get()).capacity()
+            })
+        };
+        let before = caps(&glass);
+
+        // Sorted snapshot, spilling past the trie, with a zero that deletes.
+        let sorted: Vec<(u32, u64)> = (0..5000u32).map(|i| (i * 7 + 1, u64::from(i % 5))).collect();
+        glass.replace_contents(sorted.iter().copied());
+        let reference: BTreeMap<u32, u64> = sorted.iter().copied().filter(|&(_, v)| v != 0).collect();
+        glass.assert_equivalent(&reference);
+        assert_eq!(glass.get(3), None);
+        assert_eq!(caps(&glass), before);
+
+        // Unsorted with duplicates: last write wins.
+        let mut rng = 0x2545F4914F6CDD1Du64;
+        let mut reference = BTreeMap::new();
+        let mut shuffled = Vec::new();
+        for _ in 0..3000 {
+            rng ^= rng << 13;
+            rng ^= rng >> 7;
+            rng ^= rng << 17;
+            let (k, v) = ((rng % 20_000) as u32, rng % 4);
+            shuffled.push((k, v));
+            if v == 0 {
+                reference.remove(&k);
+            } else {
+                reference.insert(k, v);
+            }
+        }
+        glass.replace_contents(shuffled);
+        glass.assert_equivalent(&reference);
+        assert_eq!(caps(&glass), before);
+
+        let mut bids = Glass::new_reversed();
+        bids.replace_contents((0..200u32).rev().map(|k| (k, 2)));
+        bids.assert_equivalent(&(0..200u32).map(|k| (k, 2)).collect());
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    fn test_free_lists_stay_consistent() {
+        let mut glass = Glass::new();
+        let mut rng = 0x9E3779B97F4A7C15u64;
+        let mut next = || {
+            rng ^= rng << 13;
+            rng ^= rng >> 7;
+            rng ^= rng << 17;
+            rng
+        };
+        for round in 0..40 {
+            for _ in 0..500 {
+                let r = next();
+                let key = (r % 30_000) as u32;
+                match r >> 60 {
+                    0..=8 => glass.insert(key, r >> 40 & 0xF),
+                    9..=12 => {
+                        glass.remove(key);
+                    }
+                    13 => {
+                        glass.remove_by_index((r >> 32) as usize % (glass.len() + 1));
+                    }
+                    14 => {
+                        glass.buy_shares(r >> 56);
+                    }
+                    _ => {
+                        glass.pop_last();
+                    }
+                }
+            }
+            if round % 10 == 9 {
+                glass.clear_below((next() % 30_000) as u32);
+            }
+            glass.debug_check_free_lists();
+        }
+        glass.clear();
+        glass.debug_check_free_lists();
+    }
+
+    #[test]
+    fn test_saturated_cache_buckets_fall_back_to_trie() {
+        // One level per leaf, 8 leaves per bucket over 512 buckets: the trie is
+        // full (4096 leaves) and every chain exceeds the probe bound, so deep
+        // leaves are only reachable through the trie descent. The rest spill.
+        let key = |bucket: u32, j: u32| (bucket + j * HT_SIZE as u32) << BITS_PER_LEVEL;
+        let mut glass = Glass::new();
+        let mut reference = BTreeMap::new();
+        for j in 0..10u32 {
+            for bucket in 0..512u32 {
+                glass.insert(key(bucket, j), u64::from(j + 1));
+                reference.insert(key(bucket, j), u64::from(j + 1));
+            }
+        }
+        assert_eq!(glass.glass_size(), MAX_SIZE);
+        let unknown = (0..512u32)
+            .flat_map(|b| (0..8).map(move |j| key(b, j) >> BITS_PER_LEVEL))
+            .filter(|&partial| glass.ht_lookup(partial) == HT_UNKNOWN)
+            .count();
+        assert!(unknown > 0, "no chain exceeded the probe bound");
+        glass.assert_equivalent(&reference);
+
+        for (i, (&k, v)) in reference.iter_mut().enumerate() {
+            if i % 3 == 0 {
+                assert!(glass.update_value(k, |q| *q += 100));
+                *v += 100;
+            }
+        }
+        glass.assert_equivalent(&reference);
+        for bucket in (0..512u32).step_by(2) {
+            let k = key(bucket, 7);
+            assert_eq!(glass.remove(k), reference.remove(&k));
+        }
+        glass.assert_equivalent(&reference);
+    }
+
+    #[test]
+    fn test_leaf_chain_stays_sorted() {
+        // Leaves created far apart, then between existing neighbours, then
+        // chain ends and middles emptied and re-created.
+        let mut glass = Glass::new();
+        assert!(glass.is_leaf_chain_sorted());
+        let blocks = [1000u32, 0, 4000, 10, 999, 2500, 1001, 3999, 64, 5];
+        for &b in &blocks {
+            glass.insert(b << BITS_PER_LEVEL, 1);
+            assert!(glass.is_leaf_chain_sorted(), "after inserting block {b}");
+        }
+        for &b in &[0u32, 4000, 999, 2500] {
+            glass.remove(b << BITS_PER_LEVEL);
+            assert!(glass.is_leaf_chain_sorted(), "after emptying block {b}");
+        }
+        for &b in &[4001u32, 2500, 0, 998] {
+            glass.insert((b << BITS_PER_LEVEL) | 63, 2);
+            assert!(glass.is_leaf_chain_sorted(), "after re-creating block {b}");
+        }
+        // Churn past the trie capacity, then evictions and refills.
+        for i in 0..6000u32 {
+            glass.insert(i.wrapping_mul(2_654_435_761) % 500_000, 1);
+        }
+        glass.remove_all(&(0..250_000).step_by(7).collect::<Vec<_>>());
+        assert!(glass.is_leaf_chain_sorted());
+
+        // Each kind of corruption is detected.
+        let (a, b) = (glass.min_leaf.get(), glass.leaf_arena[glass.min_leaf.get() as usize].next_leaf);
+        glass.leaf_arena[b as usize].prev_leaf = u32::MAX;
+        assert!(!glass.is_leaf_chain_sorted(), "broken prev link");
+        glass.leaf_arena[b as usize].prev_leaf = a;
+        assert!(glass.is_leaf_chain_sorted());
+        let ht_k = glass.leaf_arena[a as usize].ht_k;
+        glass.leaf_arena[a as usize].ht_k = glass.leaf_arena[b as usize].ht_k;
+        assert!(!glass.is_leaf_chain_sorted(), "equal neighbouring blocks");
+        glass.leaf_arena[a as usize].ht_k = ht_k;
+        glass.leaf_arena[a as usize].next_leaf = glass.leaf_arena[b as usize].next_leaf;
+        assert!(!glass.is_leaf_chain_sorted(), "skipped leaf");
+    }
+
+    #[test]
+    fn test_rebuild_hash_table_repairs_lookups() {
+        let mut glass = Glass::new();
+        let mut reference = BTreeMap::new();
+        for i in 0..5000u32 {
+            let k = i.wrapping_mul(2_654_435_761) % 1_000_000;
+            glass.insert(k, u64::from(i % 9 + 1));
+            reference.insert(k, u64::from(i % 9 + 1));
+        }
+        let (&probe, &value) = reference.iter().next().unwrap();
+        // Drop the probe's bucket, and cut another chain after its head.
+        let heads = glass.ht_heads.get_mut();
+        heads[((probe >> BITS_PER_LEVEL) as usize) & (HT_SIZE - 1)] = u32::MAX;
+        let chained = heads.iter().position(|&h| h != u32::MAX).unwrap();
+        let head = heads[chained];
+        glass.leaf_arena[head as usize].ht_next = u32::MAX;
+        assert_eq!(glass.get(probe), None, "corruption not visible");
+        assert!(glass.iter().any(|(k, _)| k == probe));
+
+        glass.rebuild_hash_table();
+        assert_eq!(glass.get(probe), Some(value));
+        glass.assert_equivalent(&reference);
+        #[cfg(debug_assertions)]
+        glass.debug_check_free_lists();
+
+        // The rebuilt table keeps working through further churn.
+        for (i, &k) in reference.keys().enumerate().step_by(5) {
+            glass.insert(k, 100 + i as u64);
+        }
+        for (i, v) in reference.values_mut().enumerate().step_by(5) {
+            *v = 100 + i as u64;
+        }
+        glass.assert_equivalent(&reference);
+    }
+
+    #[test]
+    fn test_defensive_get_heals_a_dropped_bucket() {
+        let mut glass = Glass::new();
+        let mut reference = BTreeMap::new();
+        for i in 0..5000u32 {
+            let k = i.wrapping_mul(2_654_435_761) % 1_000_000;
+            glass.insert(k, u64::from(i % 9 + 1));
+            reference.insert(k, u64::from(i % 9 + 1));
+        }
+        let (&probe, &value) = reference.iter().next().unwrap();
+        let bucket = ((probe >> BITS_PER_LEVEL) as usize) & (HT_SIZE - 1);
+        let head = glass.ht_heads.get_mut()[bucket];
+        glass.ht_heads.get_mut()[bucket] = u32::MAX;
+        assert_eq!(glass.get(probe), None, "corruption not visible");
+
+        assert_eq!(glass.defensive_get(probe), Some(value));
+        assert_eq!(glass.ht_heads.get_mut()[bucket], head, "bucket not restored");
+        assert_eq!(glass.get(probe), Some(value));
+        glass.assert_equivalent(&reference);
+
+        // Consistent glass: same answers as get, absent keys included.
+        for k in [probe, probe + 1, 2_000_000] {
+            assert_eq!(glass.defensive_get(k), reference.get(&k).copied());
+        }
+    }
+
+    #[cfg(feature = "cache-stats")]
+    #[test]
+    fn test_cache_stats_sequential_inserts_mostly_hit() {
+        let mut glass = Glass::new();
+        for k in 0..MAX_SIZE as u32 {
+            glass.insert(k, 1);
+        }
+        let (hits, misses) = glass.cache_stats();
+        assert_eq!(hits + misses, MAX_SIZE as u64);
+        assert!(hits * 100 >= (hits + misses) * 95, "{hits} hits, {misses} misses");
+
+        // Levels past a full trie go to the overflow tier, uncounted.
+        for k in 0..200u32 {
+            glass.insert(1_000_000 + k, 1);
+        }
+        assert_eq!(glass.cache_stats(), (hits, misses));
+
+        // Alternating distant prefixes defeat the cache.
+        let mut scattered = Glass::new();
+        for i in 0..1000u32 {
+            scattered.insert(if i % 2 == 0 { i } else { u32::MAX / 2 + i }, 1);
+        }
+        let (hits, misses) = scattered.cache_stats();
+        assert!(misses > hits, "{hits} hits, {misses} misses");
+    }
+
+    #[test]
+    fn test_leaf_qty_sum_kernels_match_scalar() {
+        // Random leaves of varying density (empty slots hold 0, as in the
+        // arena), including values past the 2^57 bound, which every kernel
+        // must reject; the runtime-detected kernels must agree exactly.
+        fn check<V: Quantity>(make: impl Fn(u64) -> V) {
+            let mut state = 0x9E37_79B9_7F4A_7C15u64;
+            let mut next = move || {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                state
+            };
+            for round in 0..2000 {
+                let mut leaf = LeafNode::<V>::new();
+                let density = next() % 65;
+                for slot in 0..NUM_CHILDREN {
+                    if next() % 64 < density {
+                        let v = match round % 4 {
+                            0 => next() >> 7,
+                            1 if slot == 63 => 1 << 57,
+                            _ => next() % 1000 + 1,
+                        };
+                        leaf.values[slot] = make(v.max(1));
+                        leaf.mask |= 1 << slot;
+                    }
+                }
+                let expect = leaf_qty_sum_scalar(&leaf.values);
+                let exact: u128 = leaf.values.iter().map(|v| v.to_u64() as u128).sum();
+                if let Some(q) = expect {
+                    assert_eq!(q as u128, exact);
+                }
+                #[cfg(all(target_arch = "x86_64", not(miri)))]
+                {
+                    if std::is_x86_feature_detected!("avx512f") {
+                        let got = unsafe { leaf_qty_sum_avx512(&leaf.values) };
+                        assert_eq!(got, expect, "avx512, round {round}");
+                    }
+                    if std::is_x86_feature_detected!("avx2") {
+                        let got = unsafe { leaf_qty_sum_avx2(&leaf.values) };
+                        assert_eq!(got, expect, "avx2, round {round}");
+                    }
+                }
+                let glass = Glass::<V>::with_hasher(DefaultHashBuilder::default());
+                assert_eq!(glass.leaf_volume(&leaf.values), exact);
+            }
+        }
+        check(|v| v);
+        check(|v| v as u32);
+    }
+
+    #[test]
+    fn test_prefill_preempt_capacity_avoids_regrowth() {
+        let mut glass = Glass::new();
+        for k in 0..MAX_SIZE as u32 {
+            glass.insert(k, 1);
+        }
+        glass.prefill_preempt_capacity(20_000);
+        let map_cap = unsafe { &*glass.preempt.get() }.capacity();
+        let keys_cap = unsafe { &*glass.sorted_preempt_keys.get() }.capacity();
+        assert!(map_cap >= 20_000 && keys_cap >= 20_000);
+
+        // A spill storm of far keys, with sorted-index rebuilds in between.
+        for k in 0..20_000u32 {
+            glass.insert(1_000_000 + k * 7, 3);
+            if k % 5_000 == 0 {
+                assert_eq!(glass.iter().count(), MAX_SIZE + k as usize + 1);
+            }
+        }
+        assert_eq!(glass.overflow_size(), 20_000);
+        assert_eq!(glass.max(), Some((1_000_000 + 19_999 * 7, 3)));
+        assert_eq!(unsafe { &*glass.preempt.get() }.capacity(), map_cap);
+        assert_eq!(unsafe { &*glass.sorted_preempt_keys.get() }.capacity(), keys_cap);
+    }
+
+    #[test]
+    fn test_oversized_cost_target_skips_ordered_walk() {
+        for reversed in [false, true] {
+            let mut glass = if reversed { Glass::new_reversed() } else { Glass::new() };
+            for k in 0..6000u32 {
+                glass.insert(k * 3 + 1, 1 + (k % 11) as u64);
+            }
+            glass.insert(2, u64::MAX / 4); // leaf past the 2^52 whole-leaf sums
+            let whole = glass
+                .iter()
+                .fold(0u64, |t, (p, v)| t.saturating_add((p as u64).saturating_mul(v)));
+            let volume = glass.tally.volume as u64;
+            // A fresh overflow insert leaves the sorted key index stale; the
+            // whole-book path must not need it.
+            glass.insert(100_000, 1);
+            let whole = whole + 100_000;
+            assert!(glass.preempt_dirty.get());
+            for target in [volume + 1, volume + 2, u64::MAX] {
+                assert_eq!(glass.compute_buy_cost(target), whole);
+                assert_eq!(glass.compute_sell_cost(target), whole);
+            }
+            assert!(glass.preempt_dirty.get(), "oversized target walked the sorted keys");
+            // Anything short of the whole book still takes the ordered sweep.
+            assert!(glass.compute_buy_cost(volume) < whole);
+            assert!(glass.compute_sell_cost(volume) < whole);
+            assert!(!glass.preempt_dirty.get());
+        }
+    }
+
+    #[test]
+    fn test_sorted_snapshot_cache() {
+        let mut glass = Glass::new_reversed();
+        let mut reference = BTreeMap::new();
+        for k in 0..6000u32 {
+            glass.insert(k * 5, 1 + (k % 9) as u64);
+            reference.insert(k * 5, 1 + (k % 9) as u64);
+        }
+        let expected = |r: &BTreeMap<u32, u64>| r.iter().rev().map(|(&k, &v)| (k, v)).collect::<Vec<_>>();
+        assert_eq!(glass.as_sorted_vec(), expected(&reference));
+        assert!(glass.tally.snapshot_valid.get());
+
+        // Reads, including ones that rebuild other caches, leave it valid.
+        let first = glass.as_sorted_vec().as_ptr();
+        glass.get(10);
+        glass.compute_buy_cost(u64::MAX);
+        glass.compute_sell_cost(1000);
+        assert_eq!(glass.iter().count(), 6000);
+        assert!(glass.tally.snapshot_valid.get());
+        assert_eq!(glass.as_sorted_vec().as_ptr(), first);
+        // So do writes that change nothing.
+        glass.insert(10, 3);
+        glass.remove(11);
+        assert!(glass.tally.snapshot_valid.get());
+
+        // Every kind of change invalidates it.
+        let mut rng = 0x5eed_u64;
+        for step in 0..300 {
+            rng ^= rng << 13;
+            rng ^= rng >> 7;
+            rng ^= rng << 17;
+            let k = (rng % 31_000) as u32;
+            match step % 5 {
+                0 => { glass.insert(k, 3); reference.insert(k, 3); }
+                1 => { glass.remove(k * 5 % 30_000); reference.remove(&(k * 5 % 30_000)); }
+                2 => {
+                    glass.update_value(k / 5 * 5, |v| *v += 1);
+                    if let Some(v) = reference.get_mut(&(k / 5 * 5)) { *v += 1; }
+                }
+                3 => {
+                    glass.sell_shares(7);
+                    let mut left = 7;
+                    while left > 0 {
+                        let Some(mut e) = reference.first_entry() else { break };
+                        let take = (*e.get()).min(left);
+                        *e.get_mut() -= take;
+                        left -= take;
+                        if *e.get() == 0 { e.remove(); }
+                    }
+                }
+                _ => { glass.clear_above(k); reference.retain(|&p, _| p > k); }
+            }
+            assert_eq!(glass.as_sorted_vec(), expected(&reference), "step {step}");
+        }
+        glass.clear();
+        assert!(glass.as_sorted_vec().is_empty());
+    }
+
+    #[test]
+    fn test_stats_match_standalone_metrics() {
+        let empty = Glass::new().stats();
+        assert_eq!((empty.total_len, empty.leaf_count, empty.node_count), (0, 0, 1));
+        assert_eq!((empty.min_key, empty.max_key, empty.max_collision_chain), (None, None, 0));
+
+        let mut glass = Glass::new();
+        let mut rng = 0x57a7_u64;
+        for _ in 0..6000 {
+            rng ^= rng << 13;
+            rng ^= rng >> 7;
+            rng ^= rng << 17;
+            // Groups 2^18 apart share cache-table buckets, forcing chains of
+            // up to 8 leaves (past the 5-probe budget).
+            let k = (((rng % 8) as u32) << 18) | ((rng >> 20) % 512) as u32;
+            glass.insert(k, 1 + rng % 50);
+        }
+        for k in 0..3000u32 {
+            glass.insert((1 << 30) + k * 5, 4);
+        }
+        for k in (0..3000u32).step_by(7) {
+            glass.remove((1 << 30) + k * 5);
+        }
+        glass.insert(u32::MAX, 2);
+        let stats = glass.stats();
+
+        assert_eq!(stats.trie_len, glass.glass_size());
+        assert_eq!(stats.preempt_len, glass.overflow_size());
+        assert_eq!(stats.total_len, glass.len());
+        assert_eq!(stats.total_volume, glass.iter().map(|(_, v)| v).sum::<u64>());
+        assert_eq!(stats.leaf_count, glass.iter_leaves().count());
+        assert_eq!(stats.min_key, glass.iter().next().map(|(k, _)| k));
+        assert_eq!(stats.max_key, glass.iter().last().map(|(k, _)| k));
+
+        // Internal nodes reachable from the root.
+        let mut stack = vec![(glass.root, 0)];
+        let mut nodes = 0;
+        while let Some((idx, level)) = stack.pop() {
+            nodes += 1;
+            if level < NUM_LEVELS - 2 {
+                let node = &glass.arena[idx as usize];
+                stack.extend(node.children.iter().filter(|&&c| c != u32::MAX).map(|&c| (c, level + 1)));
+            }
+        }
+        assert_eq!(stats.node_count, nodes);
+
+        let mut chains = vec![0usize; HT_SIZE];
+        let mut li = glass.min_leaf.get();
+        while li != u32::MAX {
+            chains[(glass.leaf_arena[li as usize].ht_k as usize) & (HT_SIZE - 1)] += 1;
+            li = glass.leaf_arena[li as usize].next_leaf;
+        }
+        assert_eq!(stats.max_collision_chain, *chains.iter().max().unwrap());
+        assert!(stats.max_collision_chain > 5);
+
+        assert!(stats.memory_bytes > std::mem::size_of::<Glass>() + HT_SIZE * 4);
+        glass.as_sorted_vec();
+        assert!(glass.stats().memory_bytes > stats.memory_bytes);
+    }
+
+    #[test]
+    fn test_validate_spill_invariant_detects_drift() {
+        for reversed in [false, true] {
+            let mut glass = if reversed { Glass::new_reversed() } else { Glass::new() };
+            for k in 0..6000u32 {
+                glass.insert(if reversed { u32::MAX - k * 2 } else { k * 2 }, 1);
+            }
+            assert_eq!(glass.validate_spill_invariant(), Ok(()));
+
+            // Plant an overflow level sorting inside the trie's range, as a
+            // routing bug would (internal keys: the trie holds 0..=8190).
+            let xor = glass.key_xor;
+            unsafe { (*glass.preempt.get()).insert(501, 7) };
+            let trie_max = (4095 * 2) ^ xor;
+            assert_eq!(glass.validate_spill_invariant(), Err((trie_max, 501 ^ xor)));
+            unsafe { (*glass.preempt.get()).remove(&501) };
+            assert_eq!(glass.validate_spill_invariant(), Ok(()));
+
+            // A tie (the same key in both tiers) is a violation too.
+            unsafe { (*glass.preempt.get()).insert(4095 * 2, 1) };
+            assert_eq!(glass.validate_spill_invariant(), Err((trie_max, trie_max)));
+        }
+    }
+
+    #[test]
+    fn test_counts_consistent_detects_corruption() {
+        let mut glass = Glass::new();
+        for k in 0..3000u32 {
+            glass.insert(k * 13, 1);
+        }
+        assert!(glass.counts_consistent());
+
+        // Off by one at the root, then deep in the tree.
+        glass.arena[glass.root as usize].count += 1;
+        assert!(!glass.counts_consistent());
+        glass.arena[glass.root as usize].count -= 1;
+        let leaf = glass.min_leaf.get();
+        let parent = glass.leaf_arena[leaf as usize].parent as usize;
+        glass.arena[parent].count -= 1;
+        assert!(!glass.counts_consistent());
+        glass.arena[parent].count += 1;
+
+        // A child mask bit without its link.
+        let root = glass.root as usize;
+        let free_slot = glass.arena[root].mask.trailing_ones();
+        glass.arena[root].mask |= 1 << free_slot;
+        assert!(!glass.counts_consistent());
+        glass.arena[root].mask &= !(1 << free_slot);
+        assert!(glass.counts_consistent());
+    }
+
     #[test]
     fn test_buy_shares() {
         let mut glass = Glass::new();
@@ -218,6 +850,32 @@ mod tests {
         assert_eq!(glass.get(9000), Some(1));
     }
 
+    #[test]
+    fn test_partial_buy_drops_stale_cached_path() {
+        let mut glass = Glass::new();
+        glass.insert(228, 2);
+        glass.insert(196, 4);
+        glass.buy_shares(4);
+        glass.remove(228);
+        glass.insert(99, 9);
+        assert!(glass.counts_consistent());
+        glass.insert(100, 1);
+        assert_eq!(glass.buy_shares(10), 99 * 9 + 100);
+    }
+
+    #[test]
+    fn test_partial_sell_drops_stale_cached_path() {
+        let mut glass = Glass::new();
+        glass.insert(196, 2);
+        glass.insert(228, 4);
+        glass.sell_shares(4);
+        glass.remove(196);
+        glass.insert(99, 9);
+        assert!(glass.counts_consistent());
+        glass.insert(100, 1);
+        assert_eq!(glass.sell_shares(10), 100 + 99 * 9);
+    }
+
     #[test]
     fn test_find_next_set_bit() {
         let glass = Glass::new();
@@ -235,4 +893,86 @@ mod tests {
         assert_eq!(glass.find_prev_set_bit(mask, 4), Some(1));
         assert_eq!(glass.find_prev_set_bit(mask, 1), None);
     }
+
+    #[derive(Debug, Clone)]
+    enum Op {
+        Insert(u32, u64),
+        Remove(u32),
+        // `n` consecutive levels from `start`: enough to spill past 4096.
+        InsertRun(u32, u32),
+        BuyShares(u64),
+        ClearBelow(u32),
+    }
+
+    fn op() -> impl Strategy<Value = Op> {
+        prop_oneof![
+            4 => (0..12_000u32, 1..100u64).prop_map(|(k, v)| Op::Insert(k, v)),
+            3 => (0..12_000u32).prop_map(Op::Remove),
+            1 => (0..12_000u32, 1..3_000u32).prop_map(|(k, n)| Op::InsertRun(k, n)),
+            1 => (1..5_000u64).prop_map(Op::BuyShares),
+            1 => (0..12_000u32).prop_map(Op::ClearBelow),
+        ]
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(64))]
+
+        /// Random insert/remove/buy sequences on both orientations must match a
+        /// BTreeMap after every step.
+        #[test]
+        fn prop_ops_match_btreemap(reversed: bool, ops in prop::collection::vec(op(), 1..40)) {
+            let mut glass = if reversed { Glass::new_reversed() } else { Glass::new() };
+            let mut reference = BTreeMap::new();
+            for op in ops {
+                match op {
+                    Op::Insert(k, v) => {
+                        glass.insert(k, v);
+                        reference.insert(k, v);
+                    }
+                    Op::Remove(k) => prop_assert_eq!(glass.remove(k), reference.remove(&k)),
+                    Op::InsertRun(start, n) => {
+                        for k in start..start + n {
+                            glass.insert(k, 1 + (k % 7) as u64);
+                            reference.insert(k, 1 + (k % 7) as u64);
+                        }
+                    }
+                    Op::BuyShares(shares) => {
+                        // Fills from the front of glass order.
+                        let order: Vec<u32> = if reversed {
+                            reference.keys().rev().copied().collect()
+                        } else {
+                            reference.keys().copied().collect()
+                        };
+                        let (mut left, mut cost) = (shares, 0u64);
+                        for k in order {
+                            if left == 0 {
+                                break;
+                            }
+                            let level = reference.get_mut(&k).unwrap();
+                            let take = left.min(*level);
+                            cost += take * k as u64;
+                            left -= take;
+                            *level -= take;
+                            if *level == 0 {
+                                reference.remove(&k);
+                            }
+                        }
+                        prop_assert_eq!(glass.buy_shares(shares), cost);
+                    }
+                    Op::ClearBelow(price) => {
+                        let doomed: Vec<u32> = reference
+                            .keys()
+                            .copied()
+                            .filter(|&k| if reversed { k > price } else { k < price })
+                            .collect();
+                        for k in &doomed {
+                            reference.remove(k);
+                        }
+                        prop_assert_eq!(glass.clear_below(price), doomed.len());
+                    }
+                }
+                glass.assert_equivalent(&reference);
+            }
+        }
+    }
 }
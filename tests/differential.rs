@@ -4,8 +4,13 @@
 //! (MAX_SIZE = 4096), the bounded hash-table lookup ("don't know" answers,
 //! paper §5.2) and threshold maintenance (paper §4.5).
 
-use glass_rs::Glass;
+use glass_rs::{
+    Glass, GlassU32, Location, Mutation, OrderBook, OverflowPolicy, combined_buy_cost,
+    merge_ascending, merge_ascending_with,
+};
 use std::collections::BTreeMap;
+use std::ops::Bound;
+use std::sync::{Arc, Mutex};
 
 fn oracle_min(m: &BTreeMap<u32, u64>) -> Option<(u32, u64)> {
     m.iter().next().map(|(&k, &v)| (k, v))
@@ -98,7 +103,28 @@ impl Rng {
 fn check_all(glass: &Glass, oracle: &BTreeMap<u32, u64>, universe: &[u32], ctx: &str) {
     assert_eq!(glass.min(), oracle_min(oracle), "min mismatch ({ctx})");
     assert_eq!(glass.max(), oracle_max(oracle), "max mismatch ({ctx})");
+    assert_eq!(
+        glass.min_max(),
+        oracle_min(oracle).zip(oracle_max(oracle)),
+        "min_max mismatch ({ctx})"
+    );
+    assert_eq!(
+        glass.best_price_volume(),
+        glass.min().map(|(_, v)| v),
+        "best_price_volume mismatch ({ctx})"
+    );
+    assert_eq!(
+        glass.total_volume(),
+        oracle.values().fold(0u64, |t, &v| t.saturating_add(v)),
+        "total_volume mismatch ({ctx})"
+    );
     assert_eq!(glass.len(), oracle.len(), "len mismatch ({ctx})");
+    assert!(glass.is_leaf_chain_sorted(), "leaf chain ({ctx})");
+    assert_eq!(
+        glass.validate_spill_invariant(),
+        Ok(()),
+        "tier split ({ctx})"
+    );
     assert_eq!(glass.is_empty(), oracle.is_empty(), "is_empty ({ctx})");
     let mine: Vec<(u32, u64)> = glass.iter().collect();
     let theirs: Vec<(u32, u64)> = oracle.iter().map(|(&k, &v)| (k, v)).collect();
@@ -222,6 +248,47 @@ fn boundary_keys() {
     assert_eq!(glass.max(), None);
 }
 
+/// MAX_KEY is a fully supported key in both orientations, also as the
+/// extreme of a spilled book: reads, ordering, range ends, costs, removal.
+#[test]
+fn max_key_round_trips() {
+    assert_eq!(Glass::<u64>::MAX_KEY, u32::MAX);
+    for reversed in [false, true] {
+        let mut glass = if reversed {
+            Glass::new_reversed()
+        } else {
+            Glass::new()
+        };
+        // Price 0 on a reversed glass is the slot MAX_KEY takes forward.
+        let (edge, far) = if reversed {
+            (0, 1)
+        } else {
+            (Glass::<u64>::MAX_KEY, u32::MAX - 1)
+        };
+        for k in 0..5000u32 {
+            glass.insert(if reversed { u32::MAX - k } else { k }, 1);
+        }
+        glass.insert(far, 3);
+        glass.insert(edge, 4);
+        assert_eq!(glass.get(edge), Some(4));
+        assert_eq!(glass.max(), Some((edge, 4)));
+        assert_eq!(glass.prev_level(edge), Some((far, 3)));
+        assert_eq!(
+            glass.range(far..).collect::<Vec<_>>(),
+            [(far, 3), (edge, 4)]
+        );
+        assert_eq!(glass.next_level(far), Some((edge, 4)));
+        assert_eq!(glass.len(), 5002);
+        let all = glass.total_volume();
+        assert_eq!(
+            glass.compute_buy_cost(all),
+            glass.iter().map(|(k, v)| k as u64 * v).sum()
+        );
+        assert_eq!(glass.remove(edge), Some(4));
+        assert_eq!(glass.max(), Some((far, 3)));
+    }
+}
+
 /// clear() must fully reset routing state, not just empty the containers.
 #[test]
 fn clear_resets_routing() {
@@ -434,14 +501,3812 @@ fn top_levels_snapshot() {
     assert!(buf.is_empty());
 }
 
-/// FromIterator/Extend round-trip through iter().
+/// get_range_into: exact fit, truncation, short windows, and windows that
+/// cross into the overflow tier.
 #[test]
-fn from_iterator_round_trip() {
-    let src: Vec<(u32, u64)> = (0..1000u32).map(|i| (i * 5, i as u64 + 1)).collect();
-    let glass: Glass = src.iter().copied().collect();
-    assert_eq!(glass.len(), 1000);
-    let collected: Vec<(u32, u64)> = glass.iter().collect();
-    assert_eq!(collected, src);
+fn get_range_into_fixed_window() {
+    let glass: Glass = (0..5000u32).map(|i| (i * 2, i as u64 + 1)).collect();
+    let oracle: BTreeMap<u32, u64> = glass.iter().collect();
+    let window = |lo: u32, hi: u32| -> Vec<(u32, u64)> {
+        oracle.range(lo..=hi).map(|(&k, &v)| (k, v)).collect()
+    };
+
+    // Exact fit: 8 levels in [100, 114], 8 slots.
+    let mut out = [(0u32, 0u64); 8];
+    assert_eq!(glass.get_range_into(100, 114, &mut out), 8);
+    assert_eq!(out.to_vec(), window(100, 114));
+
+    // Truncation: more levels in range than slots fills N and returns N.
+    let mut out = [(0u32, 0u64); 8];
+    assert_eq!(glass.get_range_into(100, 1000, &mut out), 8);
+    assert_eq!(out.to_vec(), window(100, 1000)[..8]);
+
+    // Short window leaves the tail slots untouched.
+    let mut out = [(7u32, 7u64); 8];
+    assert_eq!(glass.get_range_into(99, 104, &mut out), 3);
+    assert_eq!(out[..3].to_vec(), window(99, 104));
+    assert_eq!(out[3..], [(7, 7); 5]);
+
+    // Across the trie / overflow boundary (the trie holds keys < 8192).
+    let mut out = [(0u32, 0u64); 32];
+    assert_eq!(glass.get_range_into(8170, 8220, &mut out), 26);
+    assert_eq!(out[..26].to_vec(), window(8170, 8220));
+
+    // Empty and inverted windows.
+    assert_eq!(glass.get_range_into(10_001, 10_001, &mut out), 0);
+    assert_eq!(glass.get_range_into(200, 100, &mut out), 0);
+    let mut none = [(0u32, 0u64); 0];
+    assert_eq!(glass.get_range_into(0, u32::MAX, &mut none), 0);
+}
+
+/// apply_delta_batch: creates, increments, decrements, exact deletions and
+/// negative overshoot in one batch, across the trie / overflow boundary.
+/// The trie must be refilled once the batch completes.
+#[test]
+fn apply_delta_batch_matches_oracle() {
+    let mut glass = Glass::new();
+    let mut oracle = BTreeMap::new();
+    let mut rng = Rng(0x2545F4914F6CDD1D);
+
+    let explicit: [(u32, i64); 7] = [
+        (10, 5),   // create
+        (10, 3),   // increment
+        (20, 4),   // create
+        (20, -4),  // exact delete
+        (30, -7),  // negative delta on an absent level: no-op
+        (10, -20), // overshoot: saturates to 0 and deletes
+        (40, 9),   // create
+    ];
+    glass.apply_delta_batch(&explicit);
+    assert_eq!(glass.iter().collect::<Vec<_>>(), [(40, 9)]);
+    oracle.insert(40, 9);
+
+    for round in 0..20 {
+        let batch: Vec<(u32, i64)> = (0..2000)
+            .map(|_| {
+                let key = (rng.below(6000) * 2) as u32;
+                let delta = rng.below(400) as i64 - 150;
+                (key, delta)
+            })
+            .collect();
+        for &(k, d) in &batch {
+            let cur = oracle.get(&k).copied().unwrap_or(0);
+            let new = if d >= 0 {
+                cur + d as u64
+            } else {
+                cur.saturating_sub(d.unsigned_abs())
+            };
+            if new == 0 {
+                oracle.remove(&k);
+            } else {
+                oracle.insert(k, new);
+            }
+        }
+        glass.apply_delta_batch(&batch);
+
+        let keys: Vec<u32> = oracle.keys().copied().collect();
+        check_all(&glass, &oracle, &keys, &format!("delta round {round}"));
+        assert_eq!(glass.glass_size(), oracle.len().min(4096), "trie refilled");
+    }
+}
+
+/// Eq/Hash are logical: books built along different paths (different trie /
+/// overflow splits, arena layouts, cached paths) compare and hash equal.
+#[test]
+fn eq_and_hash_follow_contents() {
+    use std::collections::hash_map::DefaultHasher;
+    use std::hash::{Hash, Hasher};
+    let hash_of = |g: &Glass| {
+        let mut h = DefaultHasher::new();
+        g.hash(&mut h);
+        h.finish()
+    };
+
+    // Built ascending in one go.
+    let a: Glass = (0..5000u32).map(|i| (i * 3, i as u64 + 1)).collect();
+    // Built descending with churn: extra levels inserted then removed, and
+    // quantities overwritten, so the trie was evicted into and refilled.
+    let mut b = Glass::new();
+    for i in (0..6000u32).rev() {
+        b.insert(i * 3, 1);
+    }
+    for i in 5000..6000u32 {
+        b.remove(i * 3);
+    }
+    for i in 0..5000u32 {
+        b.insert(i * 3, i as u64 + 1);
+    }
+    assert_eq!(a, b);
+    assert_eq!(hash_of(&a), hash_of(&b));
+
+    let mut c: Glass = b.iter().collect();
+    c.update_value(42, |v| *v += 1);
+    assert_ne!(a, c);
+    c.update_value(42, |v| *v -= 1);
+    assert_eq!(a, c);
+    assert_eq!(hash_of(&a), hash_of(&c));
+
+    assert_eq!(Glass::new(), Glass::new());
+    assert_ne!(a, Glass::new());
+}
+
+/// simulate_buy must predict buy_shares exactly: same cost, and applying the
+/// touched levels to a copy yields the book the real fill leaves behind.
+#[test]
+fn simulate_buy_matches_buy_shares() {
+    let mut rng = Rng(0x94D049BB133111EB);
+    for reversed in [false, true] {
+        let fresh = || {
+            if reversed {
+                Glass::new_reversed()
+            } else {
+                Glass::new()
+            }
+        };
+        let mut book = fresh();
+        for _ in 0..6000 {
+            book.insert(rng.below(20_000) as u32, rng.below(100) + 1);
+        }
+        for shares in [0u64, 1, 57, 5_000, 120_000, u64::MAX] {
+            let (cost, touched) = book.simulate_buy(shares);
+            let mut predicted = fresh();
+            predicted.extend(book.iter());
+            for &(k, v) in &touched {
+                predicted.insert(k, v);
+            }
+            let len_before = book.len();
+            assert_eq!(book.buy_shares(shares), cost, "cost({shares})");
+            assert_eq!(book, predicted, "residual({shares}, reversed={reversed})");
+            let emptied = touched.iter().filter(|&&(_, v)| v == 0).count();
+            assert_eq!(book.len(), len_before - emptied);
+            assert!(touched.iter().rev().skip(1).all(|&(_, v)| v == 0));
+        }
+        assert!(book.is_empty());
+    }
+}
+
+/// drain_filter yields exactly the matching levels (in order), leaves exactly
+/// the others, and refills the trie when dropped. Dropping early keeps the
+/// unvisited levels.
+#[test]
+fn drain_filter_partitions_levels() {
+    let mut rng = Rng(0x8CB92BA72F3D8DD7);
+    let mut glass = Glass::new();
+    let mut oracle = BTreeMap::new();
+    for _ in 0..8000 {
+        let k = rng.below(30_000) as u32;
+        let v = rng.below(100) + 1;
+        glass.insert(k, v);
+        oracle.insert(k, v);
+    }
+    let pred = |k: u32, v: u64| (5_000..9_000).contains(&k) || v < 20;
+
+    let yielded: Vec<(u32, u64)> = glass.drain_filter(pred).collect();
+    let expected: Vec<(u32, u64)> = oracle
+        .iter()
+        .map(|(&k, &v)| (k, v))
+        .filter(|&(k, v)| pred(k, v))
+        .collect();
+    assert_eq!(yielded, expected);
+    oracle.retain(|&k, &mut v| !pred(k, v));
+    let keys: Vec<u32> = (0..30_000).collect();
+    check_all(&glass, &oracle, &keys, "drain_filter survivors");
+    assert_eq!(glass.glass_size(), oracle.len().min(4096), "trie refilled");
+
+    // Early drop: only the first three matches are removed.
+    let first: Vec<(u32, u64)> = glass.drain_filter(|_, v| v % 2 == 0).take(3).collect();
+    assert_eq!(first.len(), 3);
+    for &(k, _) in &first {
+        oracle.remove(&k);
+    }
+    check_all(&glass, &oracle, &keys, "drain_filter early drop");
+
+    // Draining everything empties the book.
+    assert_eq!(glass.drain_filter(|_, _| true).count(), oracle.len());
+    assert!(glass.is_empty());
+}
+
+/// get_with_rank agrees with a separate rank() walk and with the oracle's
+/// ordinal, across both tiers, for present and absent keys, in both
+/// orientations.
+#[test]
+fn get_with_rank_matches_rank() {
+    let mut rng = Rng(0x5851F42D4C957F2D);
+    for reversed in [false, true] {
+        let mut glass = if reversed {
+            Glass::new_reversed()
+        } else {
+            Glass::new()
+        };
+        let mut oracle = BTreeMap::new();
+        for _ in 0..7000 {
+            let k = (rng.below(12_000) * 3) as u32;
+            let v = rng.below(50) + 1;
+            glass.insert(k, v);
+            oracle.insert(k, v);
+        }
+        for i in 0..300u32 {
+            glass.remove(i * 3);
+            oracle.remove(&(i * 3));
+        }
+        let mut ordered: Vec<u32> = oracle.keys().copied().collect();
+        if reversed {
+            ordered.reverse();
+        }
+
+        for probe in (0..36_100u32).step_by(7).chain([0, u32::MAX]) {
+            let expected_rank =
+                ordered.partition_point(|&k| if reversed { k > probe } else { k < probe });
+            assert_eq!(glass.rank(probe), expected_rank, "rank({probe})");
+            assert_eq!(
+                glass.get_with_rank(probe),
+                oracle.get(&probe).map(|&v| (v, glass.rank(probe))),
+                "get_with_rank({probe}), reversed={reversed}"
+            );
+        }
+        for (i, &k) in ordered.iter().enumerate() {
+            assert_eq!(glass.get_with_rank(k).map(|(_, r)| r), Some(i));
+        }
+    }
+}
+
+/// clear_below / clear_above truncate exactly the requested side, across the
+/// trie / overflow split and at leaf boundaries, in both orientations.
+#[test]
+fn clear_below_and_above_truncate() {
+    let mut rng = Rng(0x9FB21C651E98DF25);
+    for reversed in [false, true] {
+        for round in 0..40 {
+            let (mut glass, mut lower) = if reversed {
+                (Glass::new_reversed(), Glass::new_reversed())
+            } else {
+                (Glass::new(), Glass::new())
+            };
+            let mut oracle = BTreeMap::new();
+            for _ in 0..6000 {
+                let k = rng.below(40_000) as u32;
+                let v = 1 + rng.below(9);
+                glass.insert(k, v);
+                lower.insert(k, v);
+                oracle.insert(k, v);
+            }
+            let cut = match round % 5 {
+                0 => (rng.below(625) * 64) as u32, // leaf boundary
+                1 => 0,
+                2 => u32::MAX,
+                _ => rng.below(40_000) as u32,
+            };
+            let ctx = format!("cut {cut}, reversed={reversed}");
+            // In glass order: "below" is before `cut` in iteration order.
+            let before = |k: u32| if reversed { k > cut } else { k < cut };
+
+            let expected = oracle.keys().filter(|&&k| before(k)).count();
+            assert_eq!(glass.clear_below(cut), expected, "clear_below ({ctx})");
+            let mut kept: BTreeMap<u32, u64> = oracle
+                .iter()
+                .filter(|&(&k, _)| !before(k))
+                .map(|(&k, &v)| (k, v))
+                .collect();
+            let mine: Vec<_> = glass.iter().collect();
+            let mut theirs: Vec<_> = kept.iter().map(|(&k, &v)| (k, v)).collect();
+            if reversed {
+                theirs.reverse();
+            }
+            assert_eq!(mine, theirs, "clear_below survivors ({ctx})");
+            // The key stored as u32::MAX always lives in the overflow tier.
+            let pinned = usize::from(kept.contains_key(&if reversed { 0 } else { u32::MAX }));
+            assert_eq!(
+                glass.glass_size(),
+                (kept.len() - pinned).min(4096),
+                "refilled ({ctx})"
+            );
+            if let Some((k, _)) = glass.min() {
+                assert!(!before(k), "min below cut ({ctx})");
+            }
+            // The book stays fully usable afterwards.
+            glass.insert(cut, 5);
+            kept.insert(cut, 5);
+            assert_eq!(glass.len(), kept.len(), "len after reinsert ({ctx})");
+
+            let expected = oracle.keys().filter(|&&k| !before(k)).count();
+            assert_eq!(lower.clear_above(cut), expected, "clear_above ({ctx})");
+            let mine: Vec<_> = lower.iter().collect();
+            let mut theirs: Vec<_> = oracle
+                .iter()
+                .filter(|&(&k, _)| before(k))
+                .map(|(&k, &v)| (k, v))
+                .collect();
+            if reversed {
+                theirs.reverse();
+            }
+            assert_eq!(mine, theirs, "clear_above survivors ({ctx})");
+            if let Some((k, _)) = lower.max() {
+                assert!(before(k), "max at or above cut ({ctx})");
+            }
+            assert_eq!(
+                lower.rank(cut),
+                lower.len(),
+                "rank after clear_above ({ctx})"
+            );
+        }
+    }
+}
+
+/// retain_range keeps exactly the band, including bands touching u32::MAX,
+/// inverted (empty) bands, and bands spanning the trie / overflow split.
+#[test]
+fn retain_range_keeps_exactly_the_band() {
+    let mut rng = Rng(0x3C6EF372FE94F82B);
+    for reversed in [false, true] {
+        for round in 0..30 {
+            let mut glass = if reversed {
+                Glass::new_reversed()
+            } else {
+                Glass::new()
+            };
+            let mut oracle = BTreeMap::new();
+            for _ in 0..6000 {
+                let k = rng.below(40_000) as u32;
+                let v = 1 + rng.below(9);
+                glass.insert(k, v);
+                oracle.insert(k, v);
+            }
+            glass.insert(u32::MAX, 3);
+            glass.insert(0, 4);
+            oracle.insert(u32::MAX, 3);
+            oracle.insert(0, 4);
+            let (a, b) = (rng.below(40_000) as u32, rng.below(40_000) as u32);
+            let (lo, hi) = match round % 5 {
+                0 => (a.min(b), u32::MAX),
+                1 => (0, a.max(b)),
+                2 => (a.max(b) + 1, a.min(b)), // inverted: empty in forward order
+                _ => (a.min(b), a.max(b)),
+            };
+            let (lo, hi) = if reversed { (hi, lo) } else { (lo, hi) };
+            let ctx = format!("lo {lo}, hi {hi}, reversed={reversed}");
+            let inside = |k: u32| {
+                if reversed {
+                    hi <= k && k <= lo
+                } else {
+                    lo <= k && k <= hi
+                }
+            };
+
+            let expected = oracle.keys().filter(|&&k| !inside(k)).count();
+            assert_eq!(glass.retain_range(lo, hi), expected, "removed ({ctx})");
+            let mine: Vec<_> = glass.iter().collect();
+            let mut theirs: Vec<_> = oracle
+                .iter()
+                .filter(|&(&k, _)| inside(k))
+                .map(|(&k, &v)| (k, v))
+                .collect();
+            if reversed {
+                theirs.reverse();
+            }
+            assert_eq!(mine, theirs, "survivors ({ctx})");
+            assert_eq!(glass.len(), theirs.len(), "len ({ctx})");
+        }
+    }
+}
+
+/// A cursor walked forward to the ghost and back returns through the same
+/// levels to its start, across the trie / overflow split, in both
+/// orientations, and wraps at the ghost position.
+#[test]
+fn cursor_walks_forward_and_back() {
+    let mut rng = Rng(0xA54FF53A5F1D36F1);
+    for reversed in [false, true] {
+        for size in [0, 1, 300, 6000] {
+            let mut glass = if reversed {
+                Glass::new_reversed()
+            } else {
+                Glass::new()
+            };
+            for _ in 0..size {
+                glass.insert(rng.below(40_000) as u32, 1 + rng.below(9));
+            }
+            let all: Vec<_> = glass.iter().collect();
+            let ctx = format!("size {size}, reversed={reversed}");
+            for _ in 0..10 {
+                let start = rng.below(41_000) as u32;
+                let first = all
+                    .iter()
+                    .position(|&(k, _)| if reversed { k <= start } else { k >= start })
+                    .unwrap_or(all.len());
+                let mut cur = glass.cursor_at(start);
+                for &(k, v) in &all[first..] {
+                    assert_eq!((cur.key(), cur.value()), (Some(k), Some(v)), "fwd ({ctx})");
+                    cur.move_next();
+                }
+                assert_eq!(cur.key(), None, "ghost after last ({ctx})");
+                for &(k, _) in all[first..].iter().rev() {
+                    cur.move_prev();
+                    assert_eq!(cur.key(), Some(k), "back ({ctx})");
+                }
+                assert_eq!(cur.key(), all.get(first).map(|&(k, _)| k), "start ({ctx})");
+            }
+            // Ghost wraps: next to the first level, prev to the last.
+            let mut ghost = glass.cursor_at(if reversed { u32::MAX } else { 0 });
+            ghost.move_prev();
+            assert_eq!(ghost.key(), None, "ghost ({ctx})");
+            ghost.move_next();
+            assert_eq!(
+                ghost.key(),
+                all.first().map(|&(k, _)| k),
+                "wrap next ({ctx})"
+            );
+            ghost.move_prev();
+            assert_eq!(ghost.key(), None, "back to ghost ({ctx})");
+            ghost.move_prev();
+            assert_eq!(
+                ghost.key(),
+                all.last().map(|&(k, _)| k),
+                "wrap prev ({ctx})"
+            );
+        }
+    }
+}
+
+/// A CursorMut sweep that halves every third level and removes every fifth
+/// leaves the same book as the oracle, across the tier split and in both
+/// orientations; removal lands the cursor on the next level.
+#[test]
+fn cursor_mut_edits_match_oracle() {
+    let mut rng = Rng(0x510E527FADE682D1);
+    for reversed in [false, true] {
+        let mut glass = if reversed {
+            Glass::new_reversed()
+        } else {
+            Glass::new()
+        };
+        let mut oracle = BTreeMap::new();
+        for _ in 0..6000 {
+            let (k, v) = (rng.below(40_000) as u32, 2 + rng.below(9));
+            glass.insert(k, v);
+            oracle.insert(k, v);
+        }
+        let order: Vec<_> = glass.iter().collect();
+        let mut cur = glass.cursor_at_mut(order[0].0);
+        for (i, &(k, v)) in order.iter().enumerate() {
+            assert_eq!((cur.key(), cur.value()), (Some(k), Some(v)), "i={i}");
+            if i % 5 == 0 {
+                assert_eq!(cur.remove(), Some(v));
+                oracle.remove(&k);
+                continue;
+            }
+            if i % 3 == 0 {
+                assert_eq!(cur.set(v / 2), Some(v));
+                oracle.insert(k, v / 2);
+            }
+            cur.move_next();
+        }
+        assert_eq!((cur.key(), cur.remove(), cur.set(1)), (None, None, None));
+        let want: Vec<_> = oracle.iter().map(|(&k, &v)| (k, v)).collect();
+        let mut got: Vec<_> = glass.iter().collect();
+        if reversed {
+            got.reverse();
+        }
+        assert_eq!(got, want, "reversed={reversed}");
+        assert!(glass.counts_consistent());
+        assert_eq!(glass.total_volume(), oracle.values().sum::<u64>());
+    }
+}
+
+/// volume_weighted_median is the first level in glass order whose cumulative
+/// quantity reaches half the total, across both tiers and orientations.
+#[test]
+fn volume_weighted_median_matches_oracle() {
+    let tied: Glass = [(100, 5), (101, 5)].into_iter().collect();
+    assert_eq!(tied.volume_weighted_median(), Some(100));
+    let mut tied_bids = Glass::new_reversed();
+    tied_bids.extend([(100, 5), (101, 5)]);
+    assert_eq!(tied_bids.volume_weighted_median(), Some(101));
+    assert_eq!(Glass::new().volume_weighted_median(), None);
+
+    let mut rng = Rng(0x510E527FADE682D1);
+    for reversed in [false, true] {
+        for round in 0..20 {
+            let mut glass = if reversed {
+                Glass::new_reversed()
+            } else {
+                Glass::new()
+            };
+            let mut oracle = BTreeMap::new();
+            for _ in 0..1 + rng.below(8000) {
+                let k = rng.below(40_000) as u32;
+                // Some rounds put most of the volume in the overflow tier.
+                let v = if round % 3 == 0 && k > 30_000 {
+                    1000 + rng.below(1000)
+                } else {
+                    1 + rng.below(9)
+                };
+                glass.insert(k, v);
+                oracle.insert(k, v);
+            }
+            let levels: Vec<(u32, u64)> = if reversed {
+                oracle.iter().rev().map(|(&k, &v)| (k, v)).collect()
+            } else {
+                oracle.iter().map(|(&k, &v)| (k, v)).collect()
+            };
+            let total: u64 = levels.iter().map(|&(_, v)| v).sum();
+            let mut cumulative = 0;
+            let expected = levels.iter().find_map(|&(k, v)| {
+                cumulative += v;
+                (2 * cumulative >= total).then_some(k)
+            });
+            assert_eq!(
+                glass.volume_weighted_median(),
+                expected,
+                "round {round}, reversed={reversed}"
+            );
+        }
+    }
+}
+
+/// marginal_fill_price is the level at which the cumulative quantity in
+/// glass order reaches the target: fills ending mid-level, exactly at a level
+/// boundary, in the overflow tier, and past the end of the book.
+#[test]
+fn marginal_fill_price_matches_oracle() {
+    let mut rng = Rng(0x9B05688C5BE0CD19);
+    for reversed in [false, true] {
+        let mut glass = if reversed {
+            Glass::new_reversed()
+        } else {
+            Glass::new()
+        };
+        let mut oracle = BTreeMap::new();
+        for _ in 0..7000 {
+            let k = rng.below(20_000) as u32;
+            let v = 1 + rng.below(50);
+            glass.insert(k, v);
+            oracle.insert(k, v);
+        }
+        assert!(glass.is_spilled());
+        let levels: Vec<(u32, u64)> = if reversed {
+            oracle.iter().rev().map(|(&k, &v)| (k, v)).collect()
+        } else {
+            oracle.iter().map(|(&k, &v)| (k, v)).collect()
+        };
+        let mut boundaries = Vec::with_capacity(levels.len());
+        let mut cumulative = 0;
+        for &(k, v) in &levels {
+            cumulative += v;
+            boundaries.push((cumulative, k));
+        }
+        let total = cumulative;
+        assert_eq!(glass.marginal_fill_price(0), None);
+        assert_eq!(
+            glass.marginal_fill_price(total),
+            Some(levels.last().unwrap().0)
+        );
+        assert_eq!(glass.marginal_fill_price(total + 1), None);
+        for _ in 0..500 {
+            let i = rng.below(levels.len() as u64) as usize;
+            let (end, k) = boundaries[i];
+            let ctx = format!("level {i}, reversed={reversed}");
+            assert_eq!(glass.marginal_fill_price(end), Some(k), "boundary, {ctx}");
+            if levels[i].1 > 1 {
+                assert_eq!(
+                    glass.marginal_fill_price(end - 1),
+                    Some(k),
+                    "mid-level, {ctx}"
+                );
+            }
+            if i + 1 < levels.len() {
+                let next = levels[i + 1].0;
+                assert_eq!(glass.marginal_fill_price(end + 1), Some(next), "{ctx}");
+            }
+        }
+    }
+}
+
+/// for_each_entry visits every level exactly once, trie tier first in glass
+/// order, whatever the overflow tier's hash order.
+#[test]
+fn for_each_entry_visits_every_level_once() {
+    let mut rng = Rng(0x1F83D9AB5BE0CD19);
+    for reversed in [false, true] {
+        let mut glass = if reversed {
+            Glass::new_reversed()
+        } else {
+            Glass::new()
+        };
+        let mut oracle = BTreeMap::new();
+        for _ in 0..8000 {
+            let k = rng.below(40_000) as u32;
+            let v = rng.below(10);
+            glass.insert(k, v);
+            if v == 0 {
+                oracle.remove(&k);
+            } else {
+                oracle.insert(k, v);
+            }
+        }
+        let mut seen = Vec::new();
+        glass.for_each_entry(|k, v| seen.push((k, v)));
+        let trie_prefix: Vec<_> = glass.iter().take(glass.glass_size()).collect();
+        assert_eq!(
+            &seen[..glass.glass_size()],
+            trie_prefix,
+            "reversed={reversed}"
+        );
+        seen.sort_unstable();
+        let theirs: Vec<_> = oracle.iter().map(|(&k, &v)| (k, v)).collect();
+        assert_eq!(seen, theirs, "reversed={reversed}");
+    }
+}
+
+/// floor_key / ceil_key (and their _value shortcuts) / nearest_key agree
+/// with BTreeMap range queries across both tiers; nearest ties go to the
+/// lower price in both orientations.
+#[test]
+fn floor_ceil_and_nearest_match_oracle() {
+    let mut rng = Rng(0x9B05688C2B3E6C1F);
+    for reversed in [false, true] {
+        let mut glass = if reversed {
+            Glass::new_reversed()
+        } else {
+            Glass::new()
+        };
+        let mut oracle = BTreeMap::new();
+        for _ in 0..6000 {
+            let k = rng.below(40_000) as u32 * 2;
+            glass.insert(k, 1 + rng.below(9));
+            oracle.insert(k, glass.get(k).unwrap());
+        }
+        let below = |k: u32| oracle.range(..=k).next_back().map(|(&k, &v)| (k, v));
+        let above = |k: u32| oracle.range(k..).next().map(|(&k, &v)| (k, v));
+        for q in (0..81_000).step_by(7).chain([0, u32::MAX]) {
+            let ctx = format!("key {q}, reversed={reversed}");
+            let (floor, ceil) = if reversed {
+                (above(q), below(q))
+            } else {
+                (below(q), above(q))
+            };
+            assert_eq!(glass.floor_key(q), floor, "floor ({ctx})");
+            assert_eq!(glass.ceil_key(q), ceil, "ceil ({ctx})");
+            assert_eq!(
+                glass.floor_value(q),
+                floor.map(|(_, v)| v),
+                "floor value ({ctx})"
+            );
+            assert_eq!(
+                glass.ceil_value(q),
+                ceil.map(|(_, v)| v),
+                "ceil value ({ctx})"
+            );
+            let nearest = match (below(q), above(q)) {
+                (Some(lo), Some(hi)) => Some(if q - lo.0 <= hi.0 - q { lo } else { hi }),
+                (one, None) | (None, one) => one,
+            };
+            assert_eq!(glass.nearest_key(q), nearest, "nearest ({ctx})");
+        }
+        // Keys are even: an odd query between levels two apart is a tie.
+        let (&a, _) = oracle
+            .iter()
+            .find(|&(&k, _)| oracle.contains_key(&(k + 2)))
+            .unwrap();
+        assert_eq!(glass.nearest_key(a + 1).map(|(k, _)| k), Some(a));
+    }
+    assert_eq!(Glass::new().nearest_key(5), None);
+}
+
+/// leaves_in_range counts exactly the iter_leaves blocks overlapping the band.
+#[test]
+fn leaves_in_range_matches_leaf_walk() {
+    let mut rng = Rng(0x5BE0CD191F83D9AB);
+    for reversed in [false, true] {
+        let mut glass = if reversed {
+            Glass::new_reversed()
+        } else {
+            Glass::new()
+        };
+        for _ in 0..6000 {
+            glass.insert(rng.below(60_000) as u32, 1);
+        }
+        // Blocks as real-price intervals [first, last] in glass order.
+        let last = Glass::<u64>::LEAF_WIDTH - 1;
+        let blocks: Vec<(u32, u32)> = glass
+            .iter_leaves()
+            .map(|(base, _, _)| {
+                if reversed {
+                    (!base, !(base | last))
+                } else {
+                    (base, base | last)
+                }
+            })
+            .collect();
+        for _ in 0..500 {
+            let (a, b) = (rng.below(61_000) as u32, rng.below(61_000) as u32);
+            let (lo, hi) = if (a <= b) != reversed { (a, b) } else { (b, a) };
+            let expected = blocks
+                .iter()
+                .filter(|&&(first, last)| {
+                    if reversed {
+                        first >= hi && last <= lo
+                    } else {
+                        first <= hi && last >= lo
+                    }
+                })
+                .count();
+            assert_eq!(
+                glass.leaves_in_range(lo, hi),
+                expected,
+                "lo {lo}, hi {hi}, reversed={reversed}"
+            );
+        }
+        let empty_band = if reversed {
+            (0, u32::MAX)
+        } else {
+            (u32::MAX, 0)
+        };
+        assert_eq!(glass.leaves_in_range(empty_band.0, empty_band.1), 0);
+        assert_eq!(
+            glass.leaves_in_range(empty_band.1, empty_band.0),
+            blocks.len()
+        );
+    }
+}
+
+/// from_chunks merges overlapping partial books exactly like summing into a
+/// BTreeMap, including chunks that spill into the overflow tier.
+#[test]
+fn from_chunks_merges_overlapping_books() {
+    let mut rng = Rng(0xCBBB9D5D629A292A);
+    for reversed in [false, true] {
+        let mut oracle: BTreeMap<u32, u64> = BTreeMap::new();
+        let chunks: Vec<Glass> = (0..3u32)
+            .map(|c| {
+                let mut chunk = if reversed {
+                    Glass::new_reversed()
+                } else {
+                    Glass::new()
+                };
+                for _ in 0..3000 * (c + 1) {
+                    chunk.insert(rng.below(20_000) as u32 + 5000 * c, 1 + rng.below(9));
+                }
+                for (k, v) in &chunk {
+                    *oracle.entry(k).or_insert(0) += v;
+                }
+                chunk
+            })
+            .collect();
+        let merged = Glass::from_chunks(chunks, |a, b| a + b);
+        assert_eq!(merged.is_reversed(), reversed);
+        let mine: Vec<_> = merged.iter().collect();
+        let mut theirs: Vec<_> = oracle.into_iter().collect();
+        if reversed {
+            theirs.reverse();
+        }
+        assert_eq!(mine, theirs, "reversed={reversed}");
+        assert_eq!(merged.glass_size(), 4096);
+    }
+    let empty: Glass = Glass::from_chunks(Vec::new(), |a, b| a + b);
+    assert!(empty.is_empty());
+}
+
+/// for_each_mut_in_range transforms exactly the band, across tiers and
+/// orientations; levels zeroed by the transform are removed.
+#[test]
+fn for_each_mut_in_range_touches_only_the_band() {
+    let mut rng = Rng(0x428A2F98D728AE22);
+    for reversed in [false, true] {
+        for round in 0..20 {
+            let mut glass = if reversed {
+                Glass::new_reversed()
+            } else {
+                Glass::new()
+            };
+            let mut oracle = BTreeMap::new();
+            for _ in 0..6000 {
+                let k = rng.below(40_000) as u32;
+                let v = 1 + rng.below(20);
+                glass.insert(k, v);
+                oracle.insert(k, v);
+            }
+            let (a, b) = (rng.below(41_000) as u32, rng.below(41_000) as u32);
+            let (lo, hi) = (a.min(b), a.max(b));
+            // Odd quantities are zeroed (removed), even ones halved.
+            let transform = |v: &mut u64| *v = if *v % 2 == 1 { 0 } else { *v / 2 };
+            let mut visited = Vec::new();
+            let band = if reversed { (hi, lo) } else { (lo, hi) };
+            glass.for_each_mut_in_range(band.0, band.1, |k, v| {
+                visited.push(k);
+                transform(v);
+            });
+            let mut expected_visits: Vec<u32> = oracle.range(lo..=hi).map(|(&k, _)| k).collect();
+            if reversed {
+                expected_visits.reverse();
+            }
+            assert_eq!(
+                visited, expected_visits,
+                "round {round}, reversed={reversed}"
+            );
+            for (_, v) in oracle.range_mut(lo..=hi) {
+                transform(v);
+            }
+            oracle.retain(|_, v| *v != 0);
+            let mine: Vec<_> = glass.iter().collect();
+            let mut theirs: Vec<_> = oracle.into_iter().collect();
+            if reversed {
+                theirs.reverse();
+            }
+            assert_eq!(mine, theirs, "round {round}, reversed={reversed}");
+            assert_eq!(glass.glass_size(), theirs.len().min(4096));
+        }
+    }
+}
+
+/// sum_top_n_volume equals summing the first n levels of iter(), across
+/// tiers and orientations, and the total volume for n past the end.
+#[test]
+fn sum_top_n_volume_matches_iter_prefix() {
+    let mut rng = Rng(0x7137449123EF65CD);
+    for reversed in [false, true] {
+        let mut glass = if reversed {
+            Glass::new_reversed()
+        } else {
+            Glass::new()
+        };
+        for _ in 0..6000 {
+            glass.insert(rng.below(40_000) as u32, 1 + rng.below(1000));
+        }
+        let levels: Vec<u64> = glass.iter().map(|(_, v)| v).collect();
+        for n in (0..levels.len())
+            .step_by(97)
+            .chain([1, 63, 64, 65, 4095, 4096, 4097])
+        {
+            assert_eq!(
+                glass.sum_top_n_volume(n),
+                levels[..n].iter().sum::<u64>(),
+                "n {n}, reversed={reversed}"
+            );
+        }
+        assert_eq!(glass.sum_top_n_volume(usize::MAX), glass.total_volume());
+        assert_eq!(
+            glass.sum_top_n_volume(levels.len() + 1),
+            glass.total_volume()
+        );
+    }
+}
+
+/// insert_returning_index reports the level's position in iter() order,
+/// for trie, spilled and evicting inserts in both orientations.
+#[test]
+fn insert_returning_index_matches_position() {
+    let mut rng = Rng(0xB5C0FBCFEC4D3B2F);
+    for reversed in [false, true] {
+        let mut glass = if reversed {
+            Glass::new_reversed()
+        } else {
+            Glass::new()
+        };
+        let mut oracle = BTreeMap::new();
+        for i in 0..6000 {
+            let k = rng.below(40_000) as u32;
+            let v = rng.below(10);
+            let index = glass.insert_returning_index(k, v);
+            if v == 0 {
+                oracle.remove(&k);
+            } else {
+                oracle.insert(k, v);
+            }
+            let expected = if reversed {
+                oracle.range(k + 1..).count()
+            } else {
+                oracle.range(..k).count()
+            };
+            assert_eq!(index, expected, "insert #{i} of {k}, reversed={reversed}");
+            assert_eq!(index, glass.rank(k));
+        }
+    }
+}
+
+/// take_min_n / take_max_n remove exactly the iter() prefix / suffix,
+/// including counts spanning the trie / overflow split and the whole book.
+#[test]
+fn take_min_and_max_n_remove_the_extremes() {
+    let mut rng = Rng(0x3956C25BF348B538);
+    for reversed in [false, true] {
+        for n in [0, 1, 64, 4095, 4096, 4097, 5000, usize::MAX] {
+            let mut fill = |glass: &mut Glass| {
+                for _ in 0..6000 {
+                    glass.insert(rng.below(40_000) as u32, 1 + rng.below(9));
+                }
+                glass.insert(if reversed { 0 } else { u32::MAX }, 3);
+            };
+            let (mut low, mut high) = if reversed {
+                (Glass::new_reversed(), Glass::new_reversed())
+            } else {
+                (Glass::new(), Glass::new())
+            };
+            fill(&mut low);
+            fill(&mut high);
+            let ctx = format!("n {n}, reversed={reversed}");
+
+            let all: Vec<_> = low.iter().collect();
+            let k = n.min(all.len());
+            let taken = low.take_min_n(n);
+            assert_eq!(taken, all[..k], "take_min_n ({ctx})");
+            assert_eq!(low.len(), all.len() - k, "len after take_min_n ({ctx})");
+            assert_eq!(low.iter().collect::<Vec<_>>(), all[k..], "rest ({ctx})");
+
+            let all: Vec<_> = high.iter().collect();
+            let k = n.min(all.len());
+            let taken = high.take_max_n(n);
+            assert_eq!(taken, all[all.len() - k..], "take_max_n ({ctx})");
+            assert_eq!(high.len(), all.len() - k, "len after take_max_n ({ctx})");
+            assert_eq!(
+                high.iter().collect::<Vec<_>>(),
+                all[..all.len() - k],
+                "rest ({ctx})"
+            );
+        }
+    }
+}
+
+/// compute_buy_cost_scaled rounds each level's fee-scaled notional down on
+/// its own, matching a u128 per-level oracle across tiers and orientations.
+#[test]
+fn compute_buy_cost_scaled_rounds_per_level() {
+    // Hand-computed: floor(3 * 1 / 2) + floor(5 * 1 / 2) = 1 + 2, whereas
+    // scaling the unscaled total would give floor(8 / 2) = 4.
+    let book: Glass = [(3, 1), (5, 1)].into_iter().collect();
+    assert_eq!(book.compute_buy_cost_scaled(2, 1, 2), 3);
+    assert_eq!(
+        book.compute_buy_cost_scaled(2, 1, 1),
+        book.compute_buy_cost(2)
+    );
+
+    let mut rng = Rng(0xD807AA98A3030242);
+    for reversed in [false, true] {
+        let mut glass = if reversed {
+            Glass::new_reversed()
+        } else {
+            Glass::new()
+        };
+        for _ in 0..6000 {
+            let v = if rng.below(10) == 0 {
+                u64::MAX / (1 + rng.below(1000))
+            } else {
+                1 + rng.below(500)
+            };
+            glass.insert(rng.below(40_000) as u32, v);
+        }
+        let levels: Vec<(u32, u64)> = glass.iter().collect();
+        for _ in 0..200 {
+            let target = match rng.below(3) {
+                0 => rng.below(1000),
+                1 => rng.next(),
+                _ => u64::MAX,
+            };
+            let (num, den) = (1 + rng.below(20_000), 1 + rng.below(20_000));
+            let mut left = target;
+            let mut expected = 0u128;
+            for &(p, q) in &levels {
+                let take = q.min(left);
+                let n = p as u128 * take as u128;
+                expected = expected.saturating_add(
+                    (n / den as u128) * num as u128 + (n % den as u128) * num as u128 / den as u128,
+                );
+                left -= take;
+            }
+            assert_eq!(
+                glass.compute_buy_cost_scaled(target, num, den),
+                expected.min(u64::MAX as u128) as u64,
+                "target {target}, fee {num}/{den}, reversed={reversed}"
+            );
+        }
+    }
+}
+
+/// iter_batched batches flatten back to iter(), all full but the last.
+#[test]
+fn iter_batched_flattens_to_iter() {
+    let mut rng = Rng(0x12835B0145706FBE);
+    for reversed in [false, true] {
+        let mut glass = if reversed {
+            Glass::new_reversed()
+        } else {
+            Glass::new()
+        };
+        for _ in 0..6000 {
+            glass.insert(rng.below(40_000) as u32, 1 + rng.below(9));
+        }
+        let all: Vec<_> = glass.iter().collect();
+        for size in [1, 7, 64, 4096, all.len(), all.len() + 1] {
+            let batches: Vec<_> = glass.iter_batched(size).collect();
+            assert_eq!(batches.len(), all.len().div_ceil(size), "size {size}");
+            let (last, full) = batches.split_last().unwrap();
+            assert!(full.iter().all(|b| b.len() == size), "size {size}");
+            assert!(!last.is_empty() && last.len() <= size, "size {size}");
+            assert_eq!(batches.concat(), all, "size {size}, reversed={reversed}");
+        }
+    }
+    assert_eq!(Glass::new().iter_batched(3).count(), 0);
+}
+
+/// sum_values_in_range / quantity_at_or_worse match summing the band of
+/// iter(), across tiers and orientations.
+#[test]
+fn sum_values_in_range_matches_oracle() {
+    let mut rng = Rng(0x243185BE4EE4B28C);
+    for reversed in [false, true] {
+        let mut glass = if reversed {
+            Glass::new_reversed()
+        } else {
+            Glass::new()
+        };
+        let mut oracle = BTreeMap::new();
+        for _ in 0..6000 {
+            let k = rng.below(40_000) as u32;
+            let v = 1 + rng.below(1000);
+            glass.insert(k, v);
+            oracle.insert(k, v);
+        }
+        let end = if reversed { 0 } else { u32::MAX };
+        for _ in 0..500 {
+            let (a, b) = (rng.below(41_000) as u32, rng.below(41_000) as u32);
+            let (lo, hi) = (a.min(b), a.max(b));
+            let expected: u64 = oracle.range(lo..=hi).map(|(_, &v)| v).sum();
+            let (glo, ghi) = if reversed { (hi, lo) } else { (lo, hi) };
+            let ctx = format!("lo {lo}, hi {hi}, reversed={reversed}");
+            assert_eq!(glass.sum_values_in_range(glo, ghi), expected, "{ctx}");
+            assert_eq!(
+                glass.sum_values_in_range(ghi, glo),
+                u64::from(lo == hi) * expected
+            );
+            assert_eq!(
+                glass.quantity_at_or_worse(a),
+                glass.sum_values_in_range(a, end),
+                "{ctx}"
+            );
+        }
+        assert_eq!(
+            glass.quantity_at_or_worse(if reversed { u32::MAX } else { 0 }),
+            glass.total_volume()
+        );
+    }
+}
+
+/// range_count_and_sum agrees with count_in_range and sum_values_in_range
+/// on random bands, spilled or not, in both orientations.
+#[test]
+fn range_count_and_sum_matches_separate_queries() {
+    let mut rng = Rng(0x6A09E667F3BCC909);
+    for reversed in [false, true] {
+        let mut glass = if reversed {
+            Glass::new_reversed()
+        } else {
+            Glass::new()
+        };
+        for round in 0..2 {
+            for _ in 0..4000 {
+                let k = rng.below(30_000) as u32;
+                if rng.below(4) == 0 {
+                    glass.remove(k);
+                } else {
+                    glass.insert(k, 1 + rng.below(1000));
+                }
+            }
+            assert_eq!(glass.is_spilled(), round == 1);
+            for _ in 0..300 {
+                let (lo, hi) = (rng.below(31_000) as u32, rng.below(31_000) as u32);
+                assert_eq!(
+                    glass.range_count_and_sum(lo, hi),
+                    (
+                        glass.count_in_range(lo, hi),
+                        glass.sum_values_in_range(lo, hi)
+                    ),
+                    "lo {lo}, hi {hi}, reversed={reversed}"
+                );
+            }
+            let (first, last) = if reversed {
+                (u32::MAX, 0)
+            } else {
+                (0, u32::MAX)
+            };
+            assert_eq!(
+                glass.range_count_and_sum(first, last),
+                (glass.len(), glass.total_volume())
+            );
+        }
+    }
+}
+
+/// Each OverflowPolicy at the capacity boundary: which new levels are
+/// accepted, that rejections leave the book unchanged, and that updates and
+/// deletes always apply.
+#[test]
+fn insert_with_policy_at_capacity() {
+    use OverflowPolicy::*;
+    for reversed in [false, true] {
+        // Glass-order position i is price 2i (forward) or 100_000 - 2i.
+        let price = |i: u32| if reversed { 100_000 - 2 * i } else { 2 * i };
+        let full = || {
+            let mut glass = if reversed {
+                Glass::new_reversed()
+            } else {
+                Glass::new()
+            };
+            glass.extend((0..4096).map(|i| (price(i), 1)));
+            glass
+        };
+        // An odd price between resident levels 9 and 10.
+        let inside = if reversed {
+            price(10) + 1
+        } else {
+            price(10) - 1
+        };
+        let after = price(5000);
+        for (policy, accept_inside, accept_after) in [
+            (EvictWorst, true, true),
+            (SpillNew, false, true),
+            (RejectNew, false, false),
+        ] {
+            let ctx = format!("{policy:?}, reversed={reversed}");
+            let mut glass = full();
+            let before: Vec<_> = glass.iter().collect();
+            assert_eq!(
+                glass.insert_with_policy(inside, 9, policy),
+                accept_inside,
+                "{ctx}"
+            );
+            assert_eq!(glass.get(inside).is_some(), accept_inside, "{ctx}");
+            if !accept_inside {
+                assert_eq!(glass.iter().collect::<Vec<_>>(), before, "{ctx}");
+            } else {
+                // The trie's worst level moved to the overflow tier.
+                assert_eq!(glass.overflow_size(), 1, "{ctx}");
+            }
+            assert_eq!(
+                glass.insert_with_policy(after, 9, policy),
+                accept_after,
+                "{ctx}"
+            );
+            assert_eq!(glass.get(after).is_some(), accept_after, "{ctx}");
+            assert_eq!(glass.glass_size(), 4096, "{ctx}");
+
+            // Updates and deletes always apply, in either tier.
+            assert!(glass.insert_with_policy(price(3), 7, policy), "{ctx}");
+            assert_eq!(glass.get(price(3)), Some(7));
+            if accept_after {
+                assert!(glass.insert_with_policy(after, 4, policy), "{ctx}");
+                assert_eq!(glass.get(after), Some(4));
+            }
+            assert!(glass.insert_with_policy(price(0), 0, policy), "{ctx}");
+            assert_eq!(glass.get(price(0)), None);
+            // The delete refills the trie from the overflow tier if it can;
+            // with a slot left free every policy accepts a new inner level.
+            let has_room = glass.glass_size() < 4096;
+            assert_eq!(
+                glass.insert_with_policy(price(0), 2, policy),
+                has_room || policy == EvictWorst,
+                "{ctx}"
+            );
+        }
+    }
+}
+
+/// location() agrees with the tier sizes and the spill invariant: every
+/// trie level sorts before every overflow level, in both orientations.
+#[test]
+fn location_matches_tiers() {
+    for reversed in [false, true] {
+        let mut glass = if reversed {
+            Glass::new_reversed()
+        } else {
+            Glass::new()
+        };
+        let mut rng = Rng(0x10CA_7105);
+        for _ in 0..12_000 {
+            glass.insert(rng.below(20_000) as u32, 1 + rng.below(9));
+        }
+        glass.insert(u32::MAX, 4);
+        let locs: Vec<Location> = glass.keys().map(|k| glass.location(k).unwrap()).collect();
+        let in_trie = locs.iter().filter(|&&l| l == Location::Trie).count();
+        assert_eq!(in_trie, glass.glass_size());
+        assert_eq!(locs.len() - in_trie, glass.overflow_size());
+        assert!(
+            locs.windows(2)
+                .all(|w| !(w[0] == Location::Preempt && w[1] == Location::Trie))
+        );
+
+        let best = glass.min().unwrap().0;
+        assert_eq!(glass.location(best), Some(Location::Trie));
+        let stuck = if reversed { 0 } else { u32::MAX };
+        let held = glass.get(stuck).map(|_| Location::Preempt);
+        assert_eq!(glass.location(stuck), held);
+        assert_eq!(glass.location(30_000), None);
+    }
+}
+
+/// total_volume is maintained incrementally; after every kind of mutation,
+/// in both orientations and across the trie/overflow boundary, it must equal
+/// a fresh sum.
+#[test]
+fn total_volume_tracks_every_mutation() {
+    fn fresh(glass: &Glass) -> u64 {
+        glass.values().sum()
+    }
+    for reversed in [false, true] {
+        let mut glass = if reversed {
+            Glass::new_reversed()
+        } else {
+            Glass::new()
+        };
+        let mut rng = Rng(0x70_7A1_u64 + reversed as u64);
+        for step in 0..25_000 {
+            let k = rng.below(9_000) as u32;
+            let q = 1 + rng.below(50);
+            match rng.below(24) {
+                0..=5 => glass.insert(k, q),
+                6 => {
+                    glass.remove(k);
+                }
+                7 => {
+                    glass.buy_shares(rng.below(400));
+                }
+                8 => {
+                    glass.sell_shares(rng.below(400));
+                }
+                9 => {
+                    glass.update_value(k, |v| *v = v.saturating_sub(3));
+                }
+                10 => {
+                    glass.cancel_partial(k, rng.below(10));
+                }
+                11 => glass.apply_delta_batch(&[(k, q as i64 - 25), (k + 1, 5)]),
+                12 if rng.below(50) == 0 => glass.decay_all(1 + rng.below(3), 2),
+                13 => glass.for_each_mut_in_range(k, k + 200, |_, v| *v %= 4),
+                14 if rng.below(20) == 0 => {
+                    if rng.below(2) == 0 {
+                        glass.clear_below(k);
+                    } else {
+                        glass.clear_above(k);
+                    }
+                }
+                15 => glass.insert_or_max(k, q),
+                16 => {
+                    let policy = [OverflowPolicy::SpillNew, OverflowPolicy::RejectNew]
+                        [rng.below(2) as usize];
+                    glass.insert_with_policy(k, q, policy);
+                }
+                17 if rng.below(100) == 0 => {
+                    glass.replace_contents((0..5_000u32).map(|i| (i * 2, 1 + (i % 5) as u64)))
+                }
+                18 => {
+                    glass.take_min_n(rng.below(5) as usize);
+                    glass.take_max_n(rng.below(5) as usize);
+                }
+                19 => {
+                    glass.remove_all(&[k, k + 2, k + 4]);
+                }
+                20 => {
+                    glass
+                        .drain_filter(|p, v| p % 97 == 0 || v == 1)
+                        .take(3)
+                        .for_each(drop);
+                }
+                21 if rng.below(40) == 0 => {
+                    let upper = glass.split_off(k);
+                    assert_eq!(upper.total_volume(), fresh(&upper), "split_off upper");
+                }
+                22 if rng.below(40) == 0 => glass.retain(|p, _| p % 3 != 0),
+                23 => glass.insert(rng.below(2) as u32 * u32::MAX, (1 << 40) + q),
+                _ => glass.rebalance(),
+            }
+            assert_eq!(glass.total_volume(), fresh(&glass), "step {step}");
+        }
+    }
+}
+
+/// remove_returning_neighbors reports the iter-order neighbors of the removed
+/// level, across leaf and tier boundaries and at both ends of the book.
+#[test]
+fn remove_returning_neighbors_matches_oracle() {
+    for reversed in [false, true] {
+        let mut glass = if reversed {
+            Glass::new_reversed()
+        } else {
+            Glass::new()
+        };
+        let mut oracle = BTreeMap::new();
+        let mut rng = Rng(0x4E16_4B05 + reversed as u64);
+        for _ in 0..6_000 {
+            let (k, v) = (rng.below(12_000) as u32, 1 + rng.below(20));
+            glass.insert(k, v);
+            oracle.insert(k, v);
+        }
+        for k in [0, u32::MAX] {
+            glass.insert(k, 3);
+            oracle.insert(k, 3);
+        }
+        for step in 0..3_000 {
+            let key = match step % 4 {
+                0 => glass.min().map_or(0, |(k, _)| k),
+                1 => glass.max().map_or(0, |(k, _)| k),
+                _ => rng.below(12_000) as u32,
+            };
+            let (below, above) = (
+                oracle.range(..key).next_back().map(|(&k, _)| k),
+                oracle
+                    .range((Bound::Excluded(key), Bound::Unbounded))
+                    .next()
+                    .map(|(&k, _)| k),
+            );
+            let expected = oracle.remove(&key).map(|v| {
+                if reversed {
+                    (v, above, below)
+                } else {
+                    (v, below, above)
+                }
+            });
+            assert_eq!(glass.remove_returning_neighbors(key), expected, "key {key}");
+        }
+        assert_eq!(glass.len(), oracle.len());
+        assert_eq!(glass.total_volume(), oracle.values().sum::<u64>());
+    }
+}
+
+/// insert_sorted_with_scratch matches the allocating insert_many_unsorted
+/// batch for batch (both orientations), and a buffer sized for the largest
+/// batch is reused without regrowing.
+#[test]
+fn insert_sorted_with_scratch_matches_allocating_batch() {
+    for reversed in [false, true] {
+        let new = || {
+            if reversed {
+                Glass::new_reversed()
+            } else {
+                Glass::new()
+            }
+        };
+        let (mut allocating, mut scratched) = (new(), new());
+        let mut scratch = Vec::with_capacity(2_000);
+        let mut rng = Rng(0x5C_2A7C + reversed as u64);
+        for round in 0..20 {
+            let batch: Vec<(u32, u64)> = (0..rng.below(2_000))
+                .map(|_| {
+                    let value = if rng.below(8) == 0 {
+                        0
+                    } else {
+                        1 + rng.below(90)
+                    };
+                    (rng.below(7_000) as u32 * 2, value)
+                })
+                .collect();
+            allocating.insert_many_unsorted(&batch);
+            scratched.insert_sorted_with_scratch(&batch, &mut scratch);
+            assert_eq!(
+                scratched.iter().collect::<Vec<_>>(),
+                allocating.iter().collect::<Vec<_>>(),
+                "round {round}"
+            );
+            assert_eq!(
+                scratch.capacity(),
+                2_000,
+                "scratch regrown in round {round}"
+            );
+        }
+        assert_eq!(scratched.glass_size(), allocating.glass_size());
+        assert_eq!(scratched.total_volume(), allocating.total_volume());
+    }
+}
+
+/// combined_buy_cost over books of different sizes (each spilling into its
+/// overflow tier) equals compute_buy_cost on the books merged into one, for
+/// targets from a single share to past the combined volume.
+#[test]
+fn combined_buy_cost_matches_merged_book() {
+    let mut rng = Rng(0x3C6EF372FE94F82B);
+    for reversed in [false, true] {
+        let new = || {
+            if reversed {
+                Glass::new_reversed()
+            } else {
+                Glass::new()
+            }
+        };
+        let mut merged = new();
+        let books: Vec<Glass> = [200, 5000, 9000]
+            .into_iter()
+            .map(|n| {
+                let mut book = new();
+                for _ in 0..n {
+                    book.insert(rng.below(30_000) as u32 * 3, 1 + rng.below(40));
+                }
+                for (k, v) in &book {
+                    if !merged.update_value(k, |q| *q += v) {
+                        merged.insert(k, v);
+                    }
+                }
+                book
+            })
+            .collect();
+        assert!(books[1].is_spilled() && books[2].is_spilled());
+        let refs: Vec<&Glass> = books.iter().collect();
+        let volume = merged.total_volume();
+        let targets = (0..40)
+            .map(|_| rng.below(volume))
+            .chain([0, 1, volume, volume + 9]);
+        for target in targets {
+            assert_eq!(
+                combined_buy_cost(&refs, target),
+                merged.compute_buy_cost(target),
+                "target {target}, reversed={reversed}"
+            );
+        }
+        assert_eq!(
+            combined_buy_cost(&refs[..1], volume),
+            books[0].compute_buy_cost(volume)
+        );
+    }
+    let no_books: [&Glass; 0] = [];
+    assert_eq!(combined_buy_cost(&no_books, 10), 0);
+}
+
+/// remove_range cuts exactly the band out of a spilled book, in both
+/// orientations, and backfills the trie from the overflow tier up to 4096
+/// levels; with a large refill slack the explicit hook does the backfill.
+#[test]
+fn remove_range_refills_the_trie() {
+    let mut rng = Rng(0xA54FF53A5F1D36F1);
+    for reversed in [false, true] {
+        let mut glass = if reversed {
+            Glass::new_reversed()
+        } else {
+            Glass::new()
+        };
+        let mut oracle = BTreeMap::new();
+        for _ in 0..12_000 {
+            let (k, v) = (rng.below(50_000) as u32 * 2, 1 + rng.below(9));
+            glass.insert(k, v);
+            oracle.insert(k, v);
+        }
+        assert_eq!(glass.glass_size(), 4096);
+        for _ in 0..4 {
+            let best = glass.iter().next().unwrap().0;
+            let (lo, hi) = if reversed {
+                (best, best - 3000)
+            } else {
+                (best, best + 3000)
+            };
+            let expected = oracle.range(lo.min(hi)..=lo.max(hi)).count();
+            let spilled = glass.is_spilled();
+            assert_eq!(glass.remove_range(lo, hi), (expected, spilled));
+            oracle.retain(|&k, _| k < lo.min(hi) || k > lo.max(hi));
+            assert_eq!(glass.glass_size(), oracle.len().min(4096));
+            let mine: Vec<_> = glass.iter().collect();
+            let mut theirs: Vec<_> = oracle.iter().map(|(&k, &v)| (k, v)).collect();
+            if reversed {
+                theirs.reverse();
+            }
+            assert_eq!(mine, theirs, "reversed={reversed}");
+        }
+        // An inverted band removes nothing.
+        let (a, b) = (glass.min().unwrap().0, glass.max().unwrap().0);
+        assert_eq!(glass.remove_range(b, a), (0, false));
+        assert_eq!(glass.len(), oracle.len());
+    }
+
+    let mut glass = (0..9000)
+        .map(|k| (k * 3, 1))
+        .collect::<Glass>()
+        .with_refill_slack(2048);
+    assert!(!glass.shrink_preempt_into_trie_if_underfull());
+    assert_eq!(glass.remove_range(0, 3 * 1999), (2000, false));
+    assert_eq!(glass.glass_size(), 2096);
+    assert!(glass.shrink_preempt_into_trie_if_underfull());
+    assert_eq!(glass.glass_size(), 4096);
+    assert_eq!(glass.min(), Some((6000, 1)));
+    assert_eq!(glass.len(), 7000);
+}
+
+/// compute_buy_cost starts at the first leaf's lowest occupied slot however
+/// the front of the book was last removed (single removes inside and across
+/// leaves, index removal, batch pops, truncation, partial fills), so small
+/// targets price exactly the front levels.
+#[test]
+fn compute_buy_cost_after_front_removals() {
+    let mut rng = Rng(0x510E527FADE682D1);
+    let mut glass = Glass::new();
+    let mut oracle = BTreeMap::new();
+    for _ in 0..3000 {
+        let (k, v) = (rng.below(40_000) as u32, 1 + rng.below(20));
+        glass.insert(k, v);
+        oracle.insert(k, v);
+    }
+    let cost = |oracle: &BTreeMap<u32, u64>, mut target: u64| {
+        let mut total = 0;
+        for (&k, &v) in oracle {
+            let take = v.min(target);
+            total += k as u64 * take;
+            target -= take;
+        }
+        total
+    };
+    for step in 0..300 {
+        match step % 6 {
+            0 | 1 => {
+                let k = *oracle.keys().next().unwrap();
+                assert_eq!(glass.remove(k), oracle.remove(&k));
+            }
+            2 => {
+                let (k, _) = glass.remove_by_index(0).unwrap();
+                oracle.remove(&k);
+            }
+            3 => {
+                for (k, _) in glass.take_min_n(3) {
+                    oracle.remove(&k);
+                }
+            }
+            4 => {
+                let cut = oracle.keys().nth(2).copied().unwrap();
+                glass.clear_below(cut);
+                oracle.retain(|&k, _| k >= cut);
+            }
+            _ => {
+                let front = *oracle.values().next().unwrap();
+                glass.buy_shares(front / 2 + 1);
+                let mut left = front / 2 + 1;
+                while left > 0 {
+                    let mut e = oracle.first_entry().unwrap();
+                    let take = (*e.get()).min(left);
+                    *e.get_mut() -= take;
+                    left -= take;
+                    if *e.get() == 0 {
+                        e.remove();
+                    }
+                }
+            }
+        }
+        for target in [1, 2, 5, 40, 300] {
+            assert_eq!(
+                glass.compute_buy_cost(target),
+                cost(&oracle, target),
+                "target {target} at step {step}"
+            );
+        }
+    }
+}
+
+/// into_sorted_vec yields iter()'s levels, len() of them, for spilled books
+/// in both orientations, whether or not the sorted-snapshot cache is warm.
+#[test]
+fn into_sorted_vec_matches_iter() {
+    let mut rng = Rng(0x9B05688C2B3E6C1E);
+    for reversed in [false, true] {
+        for warm in [false, true] {
+            let mut glass = if reversed {
+                Glass::new_reversed()
+            } else {
+                Glass::new()
+            };
+            for _ in 0..7000 {
+                glass.insert(rng.below(30_000) as u32 * 5, 1 + rng.below(9));
+            }
+            glass.insert(u32::MAX, 1);
+            glass.remove(rng.below(30_000) as u32 * 5);
+            if warm {
+                glass.as_sorted_vec();
+            }
+            let expected: Vec<_> = glass.iter().collect();
+            let len = glass.len();
+            let levels = glass.into_sorted_vec();
+            assert_eq!(levels.len(), len);
+            assert_eq!(levels, expected, "reversed={reversed}, warm={warm}");
+        }
+    }
+    assert!(Glass::new().into_sorted_vec().is_empty());
+}
+
+/// get_unchecked agrees with get on every present key, in both tiers and
+/// orientations.
+#[test]
+fn get_unchecked_matches_get() {
+    let mut rng = Rng(0x1F83D9ABFB41BD6B);
+    for reversed in [false, true] {
+        let mut glass = if reversed {
+            Glass::new_reversed()
+        } else {
+            Glass::new()
+        };
+        for _ in 0..8000 {
+            glass.insert(rng.below(1 << 20) as u32, 1 + rng.below(99));
+        }
+        glass.insert(0, 3);
+        glass.insert(u32::MAX, 4);
+        assert!(glass.is_spilled());
+        for (k, v) in glass.iter().collect::<Vec<_>>() {
+            assert_eq!(
+                unsafe { glass.get_unchecked(k) },
+                v,
+                "key {k}, reversed={reversed}"
+            );
+        }
+    }
+}
+
+/// In debug builds get_unchecked checks its precondition.
+#[cfg(debug_assertions)]
+#[test]
+#[should_panic(expected = "is absent")]
+fn get_unchecked_asserts_presence_in_debug() {
+    let glass: Glass = [(100, 5), (101, 2)].into_iter().collect();
+    unsafe { glass.get_unchecked(102) };
+}
+
+/// Writes right after reset_caches (which drops the insert path cache)
+/// still build the same book as an oracle, with a sound leaf chain.
+#[test]
+fn reset_caches_between_writes() {
+    let mut rng = Rng(0x5BE0CD19137E2179);
+    let mut glass = Glass::new();
+    let mut oracle = BTreeMap::new();
+    for step in 0..20_000 {
+        if rng.below(4) == 0 {
+            glass.reset_caches();
+        }
+        // Runs of nearby keys so the path cache is normally hit.
+        let k = (step / 16 * 97 + rng.below(40)) as u32 % 60_000;
+        if rng.below(5) == 0 {
+            assert_eq!(glass.remove(k), oracle.remove(&k), "step {step}");
+        } else {
+            let v = 1 + rng.below(9);
+            glass.insert(k, v);
+            oracle.insert(k, v);
+        }
+    }
+    glass.reset_caches();
+    assert_eq!(glass.iter().collect::<BTreeMap<_, _>>(), oracle);
+    assert!(glass.is_leaf_chain_sorted());
+    for (&k, &v) in &oracle {
+        assert_eq!(glass.get(k), Some(v));
+    }
+}
+
+/// trim_preempt_to keeps exactly the trie plus the best max_len overflow
+/// levels, in both orientations, and the book stays fully usable.
+#[test]
+fn trim_preempt_to_drops_the_worst_levels() {
+    let mut rng = Rng(0xCA273ECEEA26619C);
+    for reversed in [false, true] {
+        let mut glass = if reversed {
+            Glass::new_reversed()
+        } else {
+            Glass::new()
+        };
+        for _ in 0..9000 {
+            glass.insert(rng.below(100_000) as u32, 1 + rng.below(9));
+        }
+        glass.insert(if reversed { 0 } else { u32::MAX }, 5);
+        let before: Vec<_> = glass.iter().collect();
+        let overflow = glass.overflow_size();
+        for max_len in [overflow + 10, overflow, 3000, 1] {
+            let len = glass.len();
+            let dropped = glass.trim_preempt_to(max_len);
+            assert_eq!(dropped, len - glass.len());
+            assert_eq!(glass.overflow_size(), max_len.min(overflow));
+            assert_eq!(glass.iter().collect::<Vec<_>>(), before[..glass.len()]);
+            assert_eq!(glass.total_volume(), glass.iter().map(|(_, v)| v).sum());
+        }
+        assert_eq!(glass.trim_preempt_to(0), 1);
+        assert!(!glass.is_spilled());
+        let (worst, _) = glass.max().unwrap();
+        let past = if reversed { worst - 1 } else { worst + 1 };
+        glass.insert(past, 7);
+        assert_eq!(glass.max(), Some((past, 7)));
+        assert_eq!(glass.glass_size(), 4096);
+    }
+}
+
+/// The arena stats count recycled slots: removals free leaves and nodes,
+/// later inserts reuse them before growing, and clear() resets both.
+#[test]
+fn arena_stats_track_recycling() {
+    let mut glass = Glass::new();
+    // 64 leaves, each the only child of its parent node (stride: the leaf
+    // width squared, 2^12 by default).
+    let w = Glass::<u64>::LEAF_WIDTH;
+    for i in 0..64u32 {
+        glass.insert(i * w * w, 1);
+    }
+    let (leaves, freed, _) = glass.leaf_arena_stats();
+    let (nodes, nodes_freed, _) = glass.internal_arena_stats();
+    assert_eq!((leaves, freed), (64, 0));
+    assert_eq!(nodes_freed, 0);
+    assert_eq!(glass.stats().node_count, nodes);
+
+    for i in 0..40u32 {
+        glass.remove(i * w * w);
+    }
+    let (live, freed, capacity) = glass.leaf_arena_stats();
+    assert_eq!((live, freed), (24, 40));
+    assert!(capacity >= 64);
+    let (live_nodes, nodes_freed, _) = glass.internal_arena_stats();
+    assert_eq!(live_nodes + nodes_freed, nodes);
+    // Under narrower leaves, parents sharing an emptied grandparent free it too.
+    let grandparents = 40 / w as usize;
+    assert_eq!(
+        nodes_freed,
+        40 + grandparents,
+        "each emptied leaf frees its parent"
+    );
+
+    // Reinsertion recycles before growing.
+    for i in 0..40u32 {
+        glass.insert(i * w * w + 1, 1);
+    }
+    let (live, freed, _) = glass.leaf_arena_stats();
+    assert_eq!((live, freed), (64, 0));
+    let (live_nodes, nodes_freed, _) = glass.internal_arena_stats();
+    assert_eq!((live_nodes, nodes_freed), (nodes, 0));
+
+    glass.clear();
+    let (live, freed, _) = glass.leaf_arena_stats();
+    assert_eq!((live, freed), (0, 0));
+    let (live_nodes, nodes_freed, _) = glass.internal_arena_stats();
+    assert_eq!((live_nodes, nodes_freed), (1, 0));
+}
+
+/// first_absent_in_range agrees with a brute-force scan of the band on a
+/// book with fully dense runs (across leaf boundaries and into the overflow
+/// tier) and sparse stretches, in both orientations.
+#[test]
+fn first_absent_in_range_matches_scan() {
+    let mut rng = Rng(0x71374491B5C0FBCF);
+    for reversed in [false, true] {
+        let mut glass = if reversed {
+            Glass::new_reversed()
+        } else {
+            Glass::new()
+        };
+        let mut oracle = BTreeMap::new();
+        let mut put = |k: u32| {
+            glass.insert(k, 1);
+            oracle.insert(k, 1);
+        };
+        for run in 0..40u32 {
+            let start = run * 500 + rng.below(100) as u32;
+            for k in start..start + 1 + rng.below(300) as u32 {
+                put(k);
+            }
+        }
+        for _ in 0..500 {
+            put(rng.below(25_000) as u32);
+        }
+        for k in [0, 1, 2, u32::MAX - 1, u32::MAX] {
+            put(k);
+        }
+        assert!(glass.is_spilled());
+        let ordered = |a: u32, b: u32| if reversed { a >= b } else { a <= b };
+        let step = |k: u32| {
+            if reversed {
+                k.checked_sub(1)
+            } else {
+                k.checked_add(1)
+            }
+        };
+        let scan = |lo: u32, hi: u32| {
+            let mut k = Some(lo);
+            while let Some(p) = k.filter(|&p| ordered(p, hi)) {
+                if !oracle.contains_key(&p) {
+                    return Some(p);
+                }
+                k = step(p);
+            }
+            None
+        };
+        let mut queries: Vec<(u32, u32)> = (0..400)
+            .map(|_| {
+                let a = rng.below(21_000) as u32;
+                (a, a + rng.below(600) as u32)
+            })
+            .collect();
+        queries.extend([(0, 2), (0, 3), (u32::MAX - 1, u32::MAX), (0, u32::MAX)]);
+        for (a, b) in queries {
+            let (lo, hi) = if reversed { (b, a) } else { (a, b) };
+            assert_eq!(
+                glass.first_absent_in_range(lo, hi),
+                scan(lo, hi),
+                "{lo}..={hi}, reversed={reversed}"
+            );
+            if lo != hi {
+                assert_eq!(glass.first_absent_in_range(hi, lo), None, "inverted band");
+            }
+        }
+    }
+}
+
+/// bulk_update_values overwrites present keys (0 removes), skips absent
+/// ones, and leaves the same book as per-key update_value calls, across
+/// both tiers and orientations.
+#[test]
+fn bulk_update_values_matches_update_value() {
+    let mut rng = Rng(0x923F82A4AB1C5ED5);
+    for reversed in [false, true] {
+        let new = || {
+            if reversed {
+                Glass::new_reversed()
+            } else {
+                Glass::new()
+            }
+        };
+        let (mut bulk, mut single) = (new(), new());
+        for _ in 0..9000 {
+            let (k, v) = (rng.below(20_000) as u32, 1 + rng.below(9));
+            bulk.insert(k, v);
+            single.insert(k, v);
+        }
+        for round in 0..10 {
+            let keys: Vec<u32> = (0..1500).map(|_| rng.below(22_000) as u32).collect();
+            let values: Vec<u64> = keys
+                .iter()
+                .map(|_| {
+                    if rng.below(6) == 0 {
+                        0
+                    } else {
+                        1 + rng.below(50)
+                    }
+                })
+                .collect();
+            let mut expected = 0;
+            for (&k, &v) in keys.iter().zip(&values) {
+                expected += usize::from(single.update_value(k, |q| *q = v));
+            }
+            assert_eq!(
+                bulk.bulk_update_values(&keys, &values),
+                expected,
+                "round {round}"
+            );
+            assert_eq!(
+                bulk.iter().collect::<Vec<_>>(),
+                single.iter().collect::<Vec<_>>(),
+                "round {round}, reversed={reversed}"
+            );
+            assert_eq!(bulk.glass_size(), single.glass_size());
+            assert_eq!(bulk.total_volume(), single.total_volume());
+        }
+    }
+}
+
+/// compute_buy_cost_with_residual pairs compute_buy_cost with the part of
+/// the target beyond the book's total volume.
+#[test]
+fn compute_buy_cost_with_residual_reports_shortfall() {
+    let mut rng = Rng(0xE9B5DBA58189DBBC);
+    for reversed in [false, true] {
+        let mut glass = if reversed {
+            Glass::new_reversed()
+        } else {
+            Glass::new()
+        };
+        for _ in 0..6000 {
+            glass.insert(rng.below(30_000) as u32, 1 + rng.below(99));
+        }
+        let volume = glass.total_volume();
+        for target in [
+            0,
+            1,
+            volume / 3,
+            volume - 1,
+            volume,
+            volume + 1,
+            volume * 2,
+            u64::MAX,
+        ] {
+            let (cost, unfilled) = glass.compute_buy_cost_with_residual(target);
+            assert_eq!(cost, glass.compute_buy_cost(target), "target {target}");
+            assert_eq!(unfilled, target.saturating_sub(volume), "target {target}");
+        }
+    }
+    assert_eq!(Glass::new().compute_buy_cost_with_residual(7), (0, 7));
+}
+
+/// iter_spilled yields exactly the overflow tier, in glass order after the
+/// trie's last level: the tail of iter() past glass_size().
+#[test]
+fn iter_spilled_is_the_tail_of_iter() {
+    let mut rng = Rng(0x3956C25BF348B538);
+    for reversed in [false, true] {
+        let mut glass = if reversed {
+            Glass::new_reversed()
+        } else {
+            Glass::new()
+        };
+        for _ in 0..3000 {
+            glass.insert(rng.below(50_000) as u32, 1 + rng.below(9));
+        }
+        assert_eq!(glass.iter_spilled().count(), 0, "not spilled yet");
+        for _ in 0..6000 {
+            let k = rng.below(50_000) as u32;
+            if rng.below(4) == 0 {
+                glass.remove(k);
+            } else {
+                glass.insert(k, 1 + rng.below(9));
+            }
+        }
+        assert!(glass.is_spilled());
+        let spilled: Vec<_> = glass.iter_spilled().collect();
+        assert_eq!(spilled.len(), glass.overflow_size());
+        let tail: Vec<_> = glass.iter().skip(glass.glass_size()).collect();
+        assert_eq!(spilled, tail, "reversed={reversed}");
+        for (k, _) in spilled {
+            assert_eq!(glass.location(k), Some(Location::Preempt));
+        }
+    }
+}
+
+/// Repeated remove_worst drains a spilled book from the back, matching the
+/// oracle's last entry each time, with inserts mixed in that re-dirty the
+/// overflow tier's sorted index.
+#[test]
+fn remove_worst_drains_from_the_back() {
+    let mut rng = Rng(0x59F111F1B605D019);
+    for reversed in [false, true] {
+        let mut glass = if reversed {
+            Glass::new_reversed()
+        } else {
+            Glass::new()
+        };
+        let mut oracle = BTreeMap::new();
+        for _ in 0..7000 {
+            let (k, v) = (rng.below(40_000) as u32, 1 + rng.below(9));
+            glass.insert(k, v);
+            oracle.insert(k, v);
+        }
+        glass.insert(if reversed { 0 } else { u32::MAX }, 3);
+        oracle.insert(if reversed { 0 } else { u32::MAX }, 3);
+        let mut last = None;
+        while !oracle.is_empty() {
+            if rng.below(50) == 0 {
+                let (k, v) = (rng.below(40_000) as u32, 1 + rng.below(9));
+                glass.insert(k, v);
+                oracle.insert(k, v);
+                last = None;
+            }
+            let expected = if reversed {
+                oracle.pop_first()
+            } else {
+                oracle.pop_last()
+            };
+            let worst = glass.remove_worst();
+            assert_eq!(worst, expected, "reversed={reversed}");
+            // Strictly descending in glass order between inserts.
+            if let (Some(prev), Some((k, _))) = (last, worst) {
+                assert!(if reversed { k > prev } else { k < prev });
+            }
+            last = worst.map(|(k, _)| k);
+            if oracle.len() % 997 == 0 {
+                assert_eq!(glass.len(), oracle.len());
+                assert_eq!(
+                    glass.max(),
+                    if reversed {
+                        oracle.first_key_value()
+                    } else {
+                        oracle.last_key_value()
+                    }
+                    .map(|(&k, &v)| (k, v))
+                );
+                assert_eq!(glass.total_volume(), oracle.values().sum::<u64>());
+                assert_eq!(glass.validate_spill_invariant(), Ok(()));
+            }
+        }
+        assert_eq!(glass.remove_worst(), None);
+        assert!(glass.is_empty());
+    }
+}
+
+/// A dropping evict callback caps the book at the best 4096 levels: the
+/// overflow tier never grows, and each reported level is exactly what a
+/// BTreeMap capped the same way pops off its back.
+#[test]
+fn evict_callback_drops_and_reports_spills() {
+    let mut rng = Rng(0x7A41E0C93B25D816);
+    for reversed in [false, true] {
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let log = Arc::clone(&events);
+        let base = if reversed {
+            Glass::new_reversed()
+        } else {
+            Glass::new()
+        };
+        let mut glass = base.with_evict_callback(move |k, v| {
+            log.lock().unwrap().push((k, v));
+            false
+        });
+        let mut oracle = BTreeMap::new();
+        let mut expected = Vec::new();
+        for _ in 0..20_000 {
+            let k = rng.below(20_000) as u32;
+            if rng.below(5) == 0 {
+                glass.remove(k);
+                oracle.remove(&k);
+                continue;
+            }
+            let v = 1 + rng.below(9);
+            glass.insert(k, v);
+            oracle.insert(k, v);
+            if oracle.len() > 4096 {
+                let worst = if reversed {
+                    oracle.pop_first()
+                } else {
+                    oracle.pop_last()
+                };
+                expected.push(worst.unwrap());
+            }
+            assert_eq!(glass.overflow_size(), 0, "reversed={reversed}");
+        }
+        assert!(!expected.is_empty());
+        assert_eq!(*events.lock().unwrap(), expected, "reversed={reversed}");
+        let got: Vec<_> = glass.iter().collect();
+        let want: Vec<_> = if reversed {
+            oracle.into_iter().rev().collect()
+        } else {
+            oracle.into_iter().collect()
+        };
+        assert_eq!(got, want, "reversed={reversed}");
+        let volume: u64 = want.iter().map(|&(_, v)| v).sum();
+        assert_eq!(glass.total_volume(), volume);
+    }
+}
+
+/// merge_ascending matches summing both sources into one BTreeMap, for
+/// overlapping, disjoint and empty inputs; merge_ascending_with applies its
+/// combiner only to shared prices, as (a, b).
+#[test]
+fn merge_ascending_matches_oracle() {
+    let mut rng = Rng(0xBB67AE8584CAA73B);
+    let mut random = |span: u64| {
+        let mut m = BTreeMap::new();
+        for _ in 0..3000 {
+            m.insert(rng.below(span) as u32, 1 + rng.below(100));
+        }
+        m
+    };
+    let overlapping = (random(10_000), random(10_000));
+    let disjoint = (
+        random(5_000),
+        random(5_000)
+            .into_iter()
+            .map(|(k, v)| (k + 5_000, v))
+            .collect(),
+    );
+    let empty = (random(10_000), BTreeMap::new());
+    for (name, (a, b)) in [
+        ("overlapping", overlapping),
+        ("disjoint", disjoint),
+        ("empty", empty),
+    ] {
+        let mut oracle = a.clone();
+        for (&k, &v) in &b {
+            *oracle.entry(k).or_insert(0) += v;
+        }
+        let want: Vec<_> = oracle.into_iter().collect();
+        let glass: Glass = a.iter().map(|(&k, &v)| (k, v)).collect();
+        let merged: Vec<_> = merge_ascending(glass.iter(), b.clone()).collect();
+        assert_eq!(merged, want, "{name}");
+        let flipped: Vec<_> = merge_ascending(b.clone(), glass.iter()).collect();
+        assert_eq!(flipped, want, "{name}, flipped");
+
+        let diffs: Vec<_> = merge_ascending_with(a.clone(), b.clone(), |x, y| x * 1000 + y)
+            .filter(|&(k, _)| a.contains_key(&k) && b.contains_key(&k))
+            .collect();
+        let expected: Vec<_> = a
+            .iter()
+            .filter_map(|(&k, &x)| b.get(&k).map(|&y| (k, x * 1000 + y)))
+            .collect();
+        assert_eq!(diffs, expected, "{name}, combiner");
+    }
+    let huge = merge_ascending([(7, u64::MAX)], [(7, 1)]).next();
+    assert_eq!(huge, Some((7, u64::MAX)), "sum saturates");
+}
+
+/// with_preallocated_leaves reserves enough that loading `n` blocks, even
+/// spread so each needs its own internal nodes, never regrows an arena.
+#[test]
+fn with_preallocated_leaves_never_regrows() {
+    for reversed in [false, true] {
+        for n in [1usize, 37, 4096] {
+            let base = if reversed {
+                Glass::new_reversed()
+            } else {
+                Glass::new()
+            };
+            let mut glass = base.with_preallocated_leaves(n);
+            let (leaf_cap, node_cap) = (glass.leaf_arena_stats().2, glass.internal_arena_stats().2);
+            assert!(leaf_cap >= n);
+            // One level per block, each block under its own top-level nodes.
+            glass.extend((0..n as u32).map(|b| (b << 20 | 5, 1)));
+            assert_eq!(glass.leaf_arena_stats(), (n, 0, leaf_cap), "n {n}");
+            assert_eq!(glass.internal_arena_stats().2, node_cap, "n {n}");
+            // Dense blocks (as many as the 4096-level trie holds) reuse them.
+            let dense = n.min(64);
+            glass.clear();
+            glass.extend((0..dense as u32 * Glass::<u64>::LEAF_WIDTH).map(|p| (p, 1)));
+            assert_eq!(glass.leaf_arena_stats(), (dense, 0, leaf_cap), "n {n}");
+        }
+    }
+    let small = Glass::new().with_preallocated_leaves(4);
+    assert!(small.leaf_arena_stats().2 < Glass::new().leaf_arena_stats().2);
+}
+
+/// difference, intersection and symmetric_difference match set operations
+/// on the two key sets, in glass order, for overlapping spilled books in both
+/// orientations.
+#[test]
+fn set_operations_match_oracle() {
+    let mut rng = Rng(0xA54FF53A5F1D36F1);
+    for reversed in [false, true] {
+        let mut books = Vec::new();
+        for _ in 0..2 {
+            let mut glass = if reversed {
+                Glass::new_reversed()
+            } else {
+                Glass::new()
+            };
+            let mut oracle = BTreeMap::new();
+            for _ in 0..6000 {
+                let (k, v) = (rng.below(12_000) as u32, 1 + rng.below(9));
+                glass.insert(k, v);
+                oracle.insert(k, v);
+            }
+            books.push((glass, oracle));
+        }
+        let ((a, a_map), (b, b_map)) = (&books[0], &books[1]);
+        assert!(a.is_spilled() && b.is_spilled());
+        let in_order = |mut levels: Vec<(u32, u64)>| {
+            levels.sort_unstable_by_key(|&(k, _)| k);
+            if reversed {
+                levels.reverse();
+            }
+            levels
+        };
+        let a_only: Vec<_> = a_map
+            .iter()
+            .filter(|(k, _)| !b_map.contains_key(k))
+            .map(|(&k, &v)| (k, v))
+            .collect();
+        let b_only: Vec<_> = b_map
+            .iter()
+            .filter(|(k, _)| !a_map.contains_key(k))
+            .map(|(&k, &v)| (k, v))
+            .collect();
+        let both: Vec<_> = a_map
+            .iter()
+            .filter(|(k, _)| b_map.contains_key(k))
+            .map(|(&k, &v)| (k, v))
+            .collect();
+        let ctx = format!("reversed={reversed}");
+        assert!(!a_only.is_empty() && !b_only.is_empty() && !both.is_empty());
+        assert_eq!(
+            a.difference(b).collect::<Vec<_>>(),
+            in_order(a_only.clone()),
+            "{ctx}"
+        );
+        assert_eq!(
+            a.intersection(b).collect::<Vec<_>>(),
+            in_order(both),
+            "{ctx}"
+        );
+        assert_eq!(
+            a.symmetric_difference(b).collect::<Vec<_>>(),
+            in_order([a_only, b_only].concat()),
+            "{ctx}"
+        );
+
+        assert_eq!(a.difference(a).count(), 0);
+        assert!(a.intersection(a).eq(a.iter()));
+        assert_eq!(a.symmetric_difference(a).count(), 0);
+        let empty = if reversed {
+            Glass::new_reversed()
+        } else {
+            Glass::new()
+        };
+        assert!(a.difference(&empty).eq(a.iter()));
+        assert_eq!(empty.difference(a).count(), 0);
+        assert_eq!(a.intersection(&empty).count(), 0);
+        assert!(empty.symmetric_difference(a).eq(a.iter()));
+    }
+}
+
+/// Replaying the mutation log's Insert/Remove entries into an empty glass
+/// rebuilds the book after every kind of mutation, in both orientations and
+/// with an evict callback dropping levels.
+#[test]
+fn mutation_log_replays_to_the_same_book() {
+    for (reversed, dropping) in [(false, false), (true, false), (false, true)] {
+        let new = || {
+            if reversed {
+                Glass::new_reversed()
+            } else {
+                Glass::new()
+            }
+        };
+        let base = if dropping {
+            new().with_evict_callback(|_, _| false)
+        } else {
+            new()
+        };
+        let mut glass = base.observe_mutations(Vec::new());
+        let mut replayed = new();
+        let mut rng = Rng(0x1F83D9ABFB41BD6B + reversed as u64);
+        for step in 0..12_000 {
+            let k = rng.below(9_000) as u32;
+            let q = 1 + rng.below(50);
+            match rng.below(28) {
+                0..=5 => glass.insert(k, q),
+                6 => {
+                    glass.remove(k);
+                }
+                7 => {
+                    glass.buy_shares(rng.below(400));
+                }
+                8 => {
+                    glass.sell_shares(rng.below(400));
+                }
+                9 => {
+                    glass.update_value(k, |v| *v = v.saturating_sub(3));
+                }
+                10 => {
+                    glass.cancel_partial(k, rng.below(10));
+                }
+                11 => glass.apply_delta_batch(&[(k, q as i64 - 25), (k + 1, 5)]),
+                12 if rng.below(50) == 0 => glass.decay_all(1 + rng.below(3), 2),
+                13 => glass.for_each_mut_in_range(k, k + 200, |_, v| *v %= 4),
+                14 if rng.below(20) == 0 => {
+                    if rng.below(2) == 0 {
+                        glass.clear_below(k);
+                    } else {
+                        glass.clear_above(k);
+                    }
+                }
+                15 => glass.insert_or_max(k, q),
+                16 => {
+                    glass.insert_with_policy(k, q, OverflowPolicy::SpillNew);
+                    glass.insert_if_absent(k + 7, q);
+                    glass.insert_batch_no_spill(&[(k + 3, q), (k + 11, 0), (k + 5, q)]);
+                }
+                17 if rng.below(100) == 0 => {
+                    glass.replace_contents((0..5_000u32).map(|i| (i * 2, 1 + (i % 5) as u64)))
+                }
+                18 => {
+                    glass.take_min_n(rng.below(5) as usize);
+                    glass.take_max_n(rng.below(5) as usize);
+                    let stop = k;
+                    glass.remove_min_while(|p, _| p % 50 != stop % 50);
+                }
+                19 => {
+                    glass.remove_all(&[k, k + 2, k + 4]);
+                    glass.bulk_update_values(&[k + 1, k + 3], &[q, 0]);
+                }
+                20 => {
+                    glass
+                        .drain_filter(|p, v| p % 97 == 0 || v == 1)
+                        .take(3)
+                        .for_each(drop);
+                }
+                21 => {
+                    glass.remove_by_index(rng.below(50) as usize);
+                    glass.insert_returning_index(k, q);
+                    glass.remove_returning_neighbors(k + 1);
+                }
+                22 if rng.below(40) == 0 => glass.retain(|p, _| p % 3 != 0),
+                23 => glass.insert_many_unsorted(&[(k, q), (k + 64, 0), (k + 9, q)]),
+                24 => {
+                    glass.remove_worst();
+                    glass.pop_first();
+                    if let Some(mut e) = glass.last_entry() {
+                        if e.get() > 25 {
+                            e.set(q);
+                        } else {
+                            e.remove();
+                        }
+                    }
+                    if let Some(mut e) = glass.first_entry() {
+                        e.set(q);
+                    }
+                }
+                25 if rng.below(30) == 0 => {
+                    glass.trim_preempt_to(rng.below(200) as usize);
+                }
+                26 if rng.below(200) == 0 => glass.clear(),
+                27 => glass.insert(rng.below(2) as u32 * u32::MAX, q),
+                _ => glass.rebalance(),
+            }
+            // Replay in chunks, so take_mutation_log must hand over exactly
+            // the entries since the last take.
+            if step % 500 == 0 || step == 11_999 {
+                for m in glass.take_mutation_log() {
+                    match m {
+                        Mutation::Insert { key, value } => replayed.insert(key, value),
+                        Mutation::Remove { key } => {
+                            replayed.remove(key);
+                        }
+                        Mutation::Evict { .. } | Mutation::Restructure => {}
+                    }
+                }
+                assert!(
+                    replayed.iter().eq(glass.iter()),
+                    "step {step}, reversed={reversed}, dropping={dropping}"
+                );
+            }
+        }
+        if dropping {
+            assert_eq!(glass.overflow_size(), 0);
+        }
+    }
+}
+
+/// A log entry per logical change: tier moves show up only as Evict and
+/// Restructure markers, and recording is off unless asked for.
+#[test]
+fn mutation_log_reports_tier_moves_as_markers() {
+    let mut glass = Glass::new().observe_mutations(Vec::with_capacity(8192));
+    glass.extend((1..=4096).map(|p| (p, 1)));
+    glass.take_mutation_log();
+    glass.insert(0, 5);
+    assert_eq!(
+        glass.mutation_log(),
+        [
+            Mutation::Evict {
+                key: 4096,
+                value: 1
+            },
+            Mutation::Insert { key: 0, value: 5 },
+        ]
+    );
+    glass.take_mutation_log();
+    glass.remove(0);
+    assert_eq!(
+        glass.mutation_log(),
+        [Mutation::Restructure, Mutation::Remove { key: 0 }]
+    );
+    glass.take_mutation_log();
+    glass.buy_shares(3);
+    assert_eq!(
+        glass.mutation_log(),
+        [
+            Mutation::Remove { key: 1 },
+            Mutation::Remove { key: 2 },
+            Mutation::Remove { key: 3 },
+        ]
+    );
+
+    let mut quiet = Glass::new();
+    quiet.insert(1, 1);
+    assert!(quiet.mutation_log().is_empty());
+    assert!(quiet.take_mutation_log().is_empty());
+}
+
+/// capacity_remaining counts down one per distinct new level, ignores
+/// overwrites, sits at 0 once the trie is full and while it stays full.
+#[test]
+fn capacity_remaining_counts_free_trie_slots() {
+    for reversed in [false, true] {
+        let mut glass = if reversed {
+            Glass::new_reversed()
+        } else {
+            Glass::new()
+        };
+        assert_eq!(glass.capacity_remaining(), 4096);
+        for k in 0..4096u32 {
+            glass.insert(k * 3 + 1, 1);
+            glass.insert(k * 3 + 1, 2);
+            assert_eq!(glass.capacity_remaining(), 4095 - k as usize);
+        }
+        glass.insert(20_000, 1);
+        assert_eq!(glass.capacity_remaining(), 0);
+        assert_eq!(glass.overflow_size(), 1);
+        // The trie stays full while the overflow tier can refill it.
+        glass.remove(1);
+        assert_eq!(glass.capacity_remaining(), 0);
+        glass.remove(4);
+        assert_eq!(glass.capacity_remaining(), 1);
+        assert_eq!(
+            glass.capacity_remaining(),
+            4096 - glass.glass_size(),
+            "reversed={reversed}"
+        );
+    }
+}
+
+/// band_iter against an eager per-band BTreeMap aggregation, in both
+/// orientations, over a spilled book; a partial `take` yields the prefix.
+#[test]
+fn band_iter_matches_eager_histogram() {
+    let mut rng = Rng(0x510E527FADE682D1);
+    for reversed in [false, true] {
+        let mut glass = if reversed {
+            Glass::new_reversed()
+        } else {
+            Glass::new()
+        };
+        let mut oracle = BTreeMap::new();
+        for _ in 0..9000 {
+            let (k, v) = (rng.below(40_000) as u32, 1 + rng.below(9));
+            glass.insert(k, v);
+            oracle.insert(k, v);
+        }
+        glass.insert(u32::MAX, 3);
+        oracle.insert(u32::MAX, 3);
+        assert!(glass.is_spilled());
+        for width in [1, 7, 64, 1000, u32::MAX] {
+            let mut histogram = BTreeMap::<u32, (u64, usize)>::new();
+            for (&k, &v) in &oracle {
+                let band = histogram.entry(k - k % width).or_default();
+                band.0 += v;
+                band.1 += 1;
+            }
+            let mut expected: Vec<_> = histogram.into_iter().map(|(b, (q, n))| (b, q, n)).collect();
+            if reversed {
+                expected.reverse();
+            }
+            assert_eq!(glass.band_iter(width).collect::<Vec<_>>(), expected);
+            let head: Vec<_> = glass.band_iter(width).take(3).collect();
+            assert_eq!(head, expected[..expected.len().min(3)]);
+        }
+    }
+}
+
+/// insert_batch_no_spill: every new batch level is either stored or
+/// returned, nothing new reaches the overflow tier, and the stored ones are
+/// the best that fit. Books start full, part-full and spilled, in both
+/// orientations; batches mix updates, removals and duplicates.
+#[test]
+fn insert_batch_no_spill_keeps_the_best_that_fit() {
+    let mut rng = Rng(0x9B05688C2B3E6C1F);
+    for (reversed, initial) in [(false, 3000), (true, 3000), (false, 4096), (true, 5000)] {
+        let mut glass = if reversed {
+            Glass::new_reversed()
+        } else {
+            Glass::new()
+        };
+        let mut oracle = BTreeMap::new();
+        for i in 0..initial {
+            let k = i * 3 + 1;
+            glass.insert(k, 2);
+            oracle.insert(k, 2);
+        }
+        // Sorts later in glass order.
+        let after = |a: u32, b: u32| if reversed { a < b } else { a > b };
+        for _ in 0..20 {
+            let mut batch: Vec<(u32, u64)> = (0..400)
+                .map(|_| (rng.below(20_000) as u32, rng.below(6)))
+                .collect();
+            batch.push((u32::MAX * !reversed as u32, 4));
+            let before = oracle.clone();
+            let overflow_before: Vec<u32> = glass.iter_spilled().map(|(k, _)| k).collect();
+            let rejected = glass.insert_batch_no_spill(&batch);
+
+            let mut last = BTreeMap::new();
+            for &(k, v) in &batch {
+                last.insert(k, v);
+            }
+            for (&k, &v) in &last {
+                if rejected.iter().any(|&(r, _)| r == k) {
+                    assert!(v != 0 && !oracle.contains_key(&k), "rejected {k}");
+                    assert_eq!(glass.get(k), None);
+                } else if v == 0 {
+                    oracle.remove(&k);
+                } else {
+                    oracle.insert(k, v);
+                }
+            }
+            assert!(rejected.iter().all(|&(k, v)| last[&k] == v));
+            assert!(rejected.windows(2).all(|w| after(w[1].0, w[0].0)));
+            let mut expected: Vec<(u32, u64)> = oracle.iter().map(|(&k, &v)| (k, v)).collect();
+            if reversed {
+                expected.reverse();
+            }
+            assert_eq!(glass.iter().collect::<Vec<_>>(), expected);
+            // No new level went to the overflow tier.
+            assert!(
+                glass
+                    .iter_spilled()
+                    .all(|(k, _)| overflow_before.contains(&k))
+            );
+            if let Some(&(first, _)) = rejected.first() {
+                // The trie was full, or the rejects sort into the overflow tier.
+                let trie_worst = glass.iter().take(glass.glass_size()).last().map(|(k, _)| k);
+                assert!(
+                    glass.capacity_remaining() == 0 || trie_worst.is_some_and(|w| after(first, w))
+                );
+                // Every new level that was stored sorts before every reject.
+                for &k in last.keys().filter(|&k| !before.contains_key(k)) {
+                    assert!(
+                        glass.get(k).is_none() || after(first, k),
+                        "{k} kept over {first}"
+                    );
+                }
+            }
+        }
+    }
+}
+
+/// remove_min_while removes exactly the levels before the first failing
+/// predicate, calls the predicate once per removed level plus once for the
+/// level it stops at, and leaves the rest equal to the oracle, in both
+/// orientations, across the trie/overflow boundary.
+#[test]
+fn remove_min_while_stops_at_first_false() {
+    let mut rng = Rng(0x5BE0CD19137E2179);
+    for reversed in [false, true] {
+        let mut glass = if reversed {
+            Glass::new_reversed()
+        } else {
+            Glass::new()
+        };
+        let mut oracle = BTreeMap::new();
+        for _ in 0..9000 {
+            let (k, v) = (rng.below(30_000) as u32, 1 + rng.below(9));
+            glass.insert(k, v);
+            oracle.insert(k, v);
+        }
+        assert!(glass.is_spilled());
+        let in_order = |oracle: &BTreeMap<u32, u64>| {
+            let mut levels: Vec<(u32, u64)> = oracle.iter().map(|(&k, &v)| (k, v)).collect();
+            if reversed {
+                levels.reverse();
+            }
+            levels
+        };
+        for round in 0..40 {
+            let levels = in_order(&oracle);
+            // Odd rounds also stop at the first level of quantity 9.
+            let budget = rng.below(600);
+            let keep_going = |v: u64, sum: u64| (v != 9 || round % 2 == 0) && sum <= budget;
+            let (mut sum, mut calls) = (0u64, 0);
+            let taken = glass.remove_min_while(|_, v| {
+                calls += 1;
+                sum += v;
+                keep_going(v, sum)
+            });
+            let mut expected = Vec::new();
+            let mut sum = 0;
+            for &(k, v) in &levels {
+                sum += v;
+                if !keep_going(v, sum) {
+                    break;
+                }
+                expected.push((k, v));
+            }
+            assert_eq!(taken, expected, "round {round}");
+            assert_eq!(calls, taken.len() + usize::from(taken.len() < levels.len()));
+            for (k, _) in &taken {
+                oracle.remove(k);
+            }
+            assert_eq!(glass.iter().collect::<Vec<_>>(), in_order(&oracle));
+            assert_eq!(glass.total_volume(), oracle.values().sum::<u64>());
+        }
+        let rest = in_order(&oracle);
+        assert_eq!(glass.remove_min_while(|_, _| true), rest);
+        assert!(glass.is_empty());
+    }
+}
+
+/// Every write path that can set a quantity to 0 removes the level instead,
+/// so a book that zeroed prices (in either tier) compares, hashes and
+/// iterates equal to one that never had them.
+#[test]
+fn zeroed_levels_equal_absent_ones() {
+    use std::hash::{BuildHasher, RandomState};
+    for reversed in [false, true] {
+        let new = || {
+            if reversed {
+                Glass::new_reversed()
+            } else {
+                Glass::new()
+            }
+        };
+        let mut never = new();
+        never.extend((0..6000u32).map(|i| (i * 2, 2)));
+        let mut zeroed = new();
+        zeroed.extend((0..6000u32).map(|i| (i * 2, 2)));
+        // Odd prices, a band of them on each tier, zeroed by different paths.
+        for (i, base) in [101u32, 11_001].into_iter().enumerate() {
+            let ks: Vec<u32> = (0..8).map(|j| base + 2 * j).collect();
+            zeroed.extend(ks.iter().map(|&k| (k, 4)));
+            assert_ne!(zeroed, never);
+            zeroed.insert(ks[0], 0);
+            zeroed.update_value(ks[1], |v| *v = 0);
+            zeroed.apply_delta_batch(&[(ks[2], -4)]);
+            zeroed.cancel_partial(ks[3], 4);
+            zeroed.insert_with_policy(ks[4], 0, OverflowPolicy::RejectNew);
+            zeroed.bulk_update_values(&[ks[5]], &[0]);
+            assert!(zeroed.insert_batch_no_spill(&[(ks[6], 0)]).is_empty());
+            zeroed.for_each_mut_in_range(ks[7], ks[7], |_, v| *v = 0);
+            // Writing 0 at a price that never existed stores nothing either.
+            zeroed.insert(base + 1_000 + i as u32 * 2, 0);
+        }
+        assert!(zeroed.iter().all(|(_, v)| v != 0));
+        assert_eq!(zeroed, never, "reversed={reversed}");
+        let hasher = RandomState::new();
+        assert_eq!(hasher.hash_one(&zeroed), hasher.hash_one(&never));
+    }
+}
+
+/// notional_in_range against a brute-force Σ price * qty over the oracle,
+/// in both orientations, for bands inside leaves, across leaves and tiers,
+/// and with quantities large enough to need the u128 accumulator.
+#[test]
+fn notional_in_range_matches_brute_force() {
+    let mut rng = Rng(0x1F83D9AB5BE0CD19);
+    for reversed in [false, true] {
+        let mut glass = if reversed {
+            Glass::new_reversed()
+        } else {
+            Glass::new()
+        };
+        let mut oracle = BTreeMap::new();
+        for i in 0..9000 {
+            let k = rng.below(40_000) as u32;
+            let v = if i % 500 == 0 {
+                1 << 55
+            } else {
+                1 + rng.below(1000)
+            };
+            glass.insert(k, v);
+            oracle.insert(k, v);
+        }
+        for k in [0, 4_000_000_000, u32::MAX] {
+            glass.insert(k, 7);
+            oracle.insert(k, 7);
+        }
+        assert!(glass.is_spilled());
+        for _ in 0..300 {
+            let (a, b) = match rng.below(4) {
+                0 => (0, u32::MAX),
+                1 => {
+                    let a = rng.below(40_000) as u32;
+                    (a, a + rng.below(64) as u32)
+                }
+                _ => (rng.below(45_000) as u32, rng.below(45_000) as u32),
+            };
+            let (lo, hi) = (a.min(b), a.max(b));
+            let expected: u128 = oracle
+                .range(lo..=hi)
+                .map(|(&k, &v)| k as u128 * v as u128)
+                .sum();
+            let expected = expected.min(u64::MAX as u128) as u64;
+            // Glass order: a reversed glass takes the band high to low.
+            let got = if reversed {
+                glass.notional_in_range(hi, lo)
+            } else {
+                glass.notional_in_range(lo, hi)
+            };
+            assert_eq!(got, expected, "{lo}..={hi}, reversed={reversed}");
+        }
+    }
+}
+
+/// rebuild_with_capacity shrinks and grows a spilled book in place: the
+/// contents never change, the trie holds exactly the best `new_max` levels
+/// afterwards, and later writes respect the new capacity.
+#[test]
+fn rebuild_with_capacity_repartitions_in_place() {
+    let mut rng = Rng(0xCBBB9D5D629A292A);
+    for reversed in [false, true] {
+        let mut glass = if reversed {
+            Glass::new_reversed()
+        } else {
+            Glass::new()
+        };
+        let mut oracle = BTreeMap::new();
+        for _ in 0..9000 {
+            let (k, v) = (rng.below(20_000) as u32, 1 + rng.below(9));
+            glass.insert(k, v);
+            oracle.insert(k, v);
+        }
+        assert!(glass.is_spilled());
+        let in_order = |oracle: &BTreeMap<u32, u64>| {
+            let mut levels: Vec<(u32, u64)> = oracle.iter().map(|(&k, &v)| (k, v)).collect();
+            if reversed {
+                levels.reverse();
+            }
+            levels
+        };
+        // The level stored as u32::MAX never takes a trie slot.
+        let pinned = if reversed { 0 } else { u32::MAX };
+        let eligible =
+            |oracle: &BTreeMap<u32, u64>| oracle.len() - oracle.contains_key(&pinned) as usize;
+        for cap in [1000, 1, 12_000, 4096, 300, 7000] {
+            glass.rebuild_with_capacity(cap);
+            let expected = in_order(&oracle);
+            assert_eq!(glass.glass_size(), cap.min(eligible(&oracle)), "cap {cap}");
+            assert_eq!(glass.capacity_remaining(), cap - glass.glass_size());
+            assert_eq!(glass.validate_spill_invariant(), Ok(()), "cap {cap}");
+            assert_eq!(glass.iter().collect::<Vec<_>>(), expected);
+            assert_eq!(glass.total_volume(), oracle.values().sum::<u64>());
+            for _ in 0..2000 {
+                let k = rng.below(20_000) as u32;
+                if rng.below(3) == 0 {
+                    glass.remove(k);
+                    oracle.remove(&k);
+                } else {
+                    let v = 1 + rng.below(9);
+                    glass.insert(k, v);
+                    oracle.insert(k, v);
+                }
+                assert!(glass.glass_size() <= cap);
+            }
+            glass.rebalance();
+            assert_eq!(glass.glass_size(), cap.min(eligible(&oracle)), "cap {cap}");
+            assert_eq!(glass.iter().collect::<Vec<_>>(), in_order(&oracle));
+        }
+    }
+}
+
+/// first_entry/last_entry: `remove` pops the same level as
+/// pop_first/pop_last, and reads and writes through the handle match the
+/// oracle, in both orientations, while the book drains across the tiers.
+#[test]
+fn extreme_entries_match_pops_and_oracle() {
+    let mut rng = Rng(0x6A09E667BB67AE85);
+    for reversed in [false, true] {
+        let new = || {
+            if reversed {
+                Glass::new_reversed()
+            } else {
+                Glass::new()
+            }
+        };
+        let (mut glass, mut twin) = (new(), new());
+        let mut oracle = BTreeMap::new();
+        for _ in 0..7000 {
+            let (k, v) = (rng.below(15_000) as u32, 1 + rng.below(9));
+            glass.insert(k, v);
+            twin.insert(k, v);
+            oracle.insert(k, v);
+        }
+        for k in [0, u32::MAX] {
+            glass.insert(k, 4);
+            twin.insert(k, 4);
+            oracle.insert(k, 4);
+        }
+        // Glass-order first and last of the oracle.
+        let ends = |oracle: &BTreeMap<u32, u64>| {
+            let (lo, hi) = (oracle.first_key_value(), oracle.last_key_value());
+            let (first, last) = if reversed { (hi, lo) } else { (lo, hi) };
+            (first.map(|(&k, &v)| (k, v)), last.map(|(&k, &v)| (k, v)))
+        };
+        while !oracle.is_empty() {
+            let (first, last) = ends(&oracle);
+            let front = rng.below(2) == 0;
+            let mut entry = if front {
+                glass.first_entry()
+            } else {
+                glass.last_entry()
+            }
+            .unwrap();
+            let (k, v) = if front { first } else { last }.unwrap();
+            assert_eq!((entry.key(), entry.get()), (k, v));
+            if rng.below(3) == 0 {
+                let q = 1 + rng.below(9);
+                assert_eq!(entry.set(q), v);
+                assert_eq!(entry.get(), q);
+                twin.insert(k, q);
+                oracle.insert(k, q);
+            } else {
+                let popped = if front {
+                    twin.pop_first()
+                } else {
+                    twin.pop_last()
+                };
+                assert_eq!(Some((k, entry.remove())), popped);
+                oracle.remove(&k);
+            }
+            assert_eq!(glass.len(), oracle.len());
+            if oracle.len() % 500 == 0 {
+                assert_eq!(glass.total_volume(), oracle.values().sum::<u64>());
+                assert!(glass == twin, "reversed={reversed}");
+                assert_eq!(glass.validate_spill_invariant(), Ok(()));
+            }
+        }
+        assert!(glass.first_entry().is_none() && glass.last_entry().is_none());
+    }
+}
+
+/// bulk_get_sorted against per-key get for sorted query runs that are dense
+/// (same leaf), sparse (re-seeks) and reach into the overflow tier, with
+/// repeats, in both orientations.
+#[test]
+fn bulk_get_sorted_matches_get() {
+    let mut rng = Rng(0x3C6EF372A54FF53A);
+    for reversed in [false, true] {
+        let mut glass = if reversed {
+            Glass::new_reversed()
+        } else {
+            Glass::new()
+        };
+        for _ in 0..8000 {
+            glass.insert(rng.below(200_000) as u32, 1 + rng.below(9));
+        }
+        glass.insert(0, 3);
+        glass.insert(u32::MAX, 3);
+        assert!(glass.is_spilled());
+        let mut out = vec![Some(1)];
+        for round in 0..60 {
+            let span = [300, 20_000, 400_000][round % 3];
+            let mut keys: Vec<u32> = (0..rng.below(500) as usize)
+                .map(|_| rng.below(span) as u32)
+                .collect();
+            if round % 5 == 0 {
+                keys.extend([0, u32::MAX]);
+            }
+            keys.sort_unstable();
+            if reversed {
+                keys.reverse();
+            }
+            glass.bulk_get_sorted(&keys, &mut out);
+            let expected: Vec<Option<u64>> = keys.iter().map(|&k| glass.get(k)).collect();
+            assert_eq!(out, expected, "round {round}, reversed={reversed}");
+        }
+    }
+}
+
+/// total_notional is maintained incrementally; after every mutation it must
+/// equal Σ price * qty recomputed from the levels, in both orientations and
+/// across partial fills, evictions and trie resizes.
+#[test]
+fn total_notional_tracks_every_mutation() {
+    fn fresh(glass: &Glass) -> u128 {
+        glass.iter().map(|(p, q)| p as u128 * q as u128).sum()
+    }
+    for reversed in [false, true] {
+        let glass = if reversed {
+            Glass::new_reversed()
+        } else {
+            Glass::new()
+        };
+        let mut glass = glass.with_evict_callback(|p, _| p % 2 == 0);
+        let mut rng = Rng(0x7071_0A11 + reversed as u64);
+        for step in 0..20_000 {
+            let k = rng.below(9_000) as u32;
+            let q = 1 + rng.below(50);
+            match rng.below(20) {
+                0..=4 => glass.insert(k, q),
+                5 => {
+                    glass.remove(k);
+                }
+                6 => {
+                    glass.buy_shares(rng.below(400));
+                }
+                7 => {
+                    glass.sell_shares(rng.below(400));
+                }
+                8 => {
+                    glass.update_value(k, |v| *v = v.saturating_sub(3));
+                }
+                9 => glass.apply_delta_batch(&[(k, q as i64 - 25), (k + 1, 5)]),
+                10 if rng.below(50) == 0 => glass.decay_all(1 + rng.below(3), 2),
+                11 => glass.for_each_mut_in_range(k, k + 200, |_, v| *v %= 4),
+                12 if rng.below(20) == 0 => {
+                    glass.clear_below(k);
+                }
+                13 if rng.below(40) == 0 => {
+                    glass.rebuild_with_capacity(1 + rng.below(6_000) as usize)
+                }
+                14 => {
+                    if let Some(mut e) = glass.first_entry() {
+                        e.set(q);
+                    }
+                    if let Some(e) = glass.last_entry() {
+                        e.remove();
+                    }
+                }
+                15 => {
+                    glass.insert_batch_no_spill(&[(k, q), (k + 64, q + 1)]);
+                }
+                16 => {
+                    glass.remove_min_while(|p, _| p % 5 != 0);
+                }
+                17 => {
+                    glass.trim_preempt_to(rng.below(300) as usize);
+                }
+                // Quantities too large for the blockwise leaf sums.
+                18 => glass.insert(k, (1 << 60) + q),
+                _ => glass.insert(rng.below(2) as u32 * u32::MAX, (1 << 40) + q),
+            }
+            assert_eq!(glass.total_notional(), fresh(&glass), "step {step}");
+        }
+    }
+}
+
+/// iter_leaves blocks (and for_each_leaf_compacted's packed ones)
+/// reconstruct exactly the trie tier of iter(), with zeroed dead slots,
+/// after churn that spills and partially drains leaves.
+#[test]
+fn iter_leaves_reconstructs_trie_tier() {
+    let mut rng = Rng(0x2545F4914F6CDD1D);
+    for reversed in [false, true] {
+        let mut glass = if reversed {
+            Glass::new_reversed()
+        } else {
+            Glass::new()
+        };
+        for k in 0..6000 {
+            glass.insert(k * 3, 1 + rng.below(50));
+        }
+        for _ in 0..8000 {
+            let k = rng.below(20_000) as u32;
+            match rng.below(10) {
+                0..=5 => {
+                    glass.insert(k, 1 + rng.below(50));
+                }
+                6..=8 => {
+                    glass.remove(k);
+                }
+                _ => {
+                    glass.buy_shares(rng.below(200));
+                }
+            }
+        }
+        assert!(glass.len() > glass.glass_size(), "test should spill");
+
+        let mut rebuilt = Vec::new();
+        let mut last_base = None;
+        for (base, mask, values) in glass.iter_leaves() {
+            assert_eq!(
+                base % Glass::<u64>::LEAF_WIDTH,
+                0,
+                "base key is leaf-aligned"
+            );
+            assert!(last_base < Some(base), "leaves out of order");
+            last_base = Some(base);
+            assert_ne!(mask, 0, "empty leaf left in the chain");
+            for (i, &v) in values.iter().enumerate() {
+                if mask & (1 << i) != 0 {
+                    let price = (base | i as u32) ^ if reversed { u32::MAX } else { 0 };
+                    rebuilt.push((price, v));
+                } else {
+                    assert_eq!(v, 0, "dead slot {i} of leaf {base} not zeroed");
+                }
+            }
+        }
+        let expected: Vec<_> = glass.iter().take(glass.glass_size()).collect();
+        assert_eq!(rebuilt, expected, "reversed={reversed}");
+
+        // The packed view: j-th value belongs to the j-th set bit.
+        let mut packed = Vec::new();
+        glass.for_each_leaf_compacted(|base, mask, values| {
+            assert_eq!(values.len(), mask.count_ones() as usize);
+            let slots = (0..64).filter(|i| mask & (1 << i) != 0);
+            for (i, &v) in slots.zip(values) {
+                packed.push(((base | i) ^ if reversed { u32::MAX } else { 0 }, v));
+            }
+        });
+        assert_eq!(packed, expected, "compacted, reversed={reversed}");
+    }
+}
+
+/// Fills against levels in `order`, with exact u128 cost; returns the cost
+/// saturated to u64 and the levels left afterwards.
+fn oracle_fill_u128(order: &[(u32, u64)], mut shares: u64) -> (u64, Vec<(u32, u64)>) {
+    let mut cost = 0u128;
+    let mut left = Vec::new();
+    for &(p, q) in order {
+        let take = q.min(shares);
+        cost += p as u128 * take as u128;
+        shares -= take;
+        if q > take {
+            left.push((p, q - take));
+        }
+    }
+    (cost.min(u64::MAX as u128) as u64, left)
+}
+
+/// Quantities near u64::MAX must not wrap the whole-leaf sums: costs match
+/// exact u128 math (saturated to u64) and fills consume exactly `shares`.
+#[test]
+fn huge_quantities_match_u128_costs() {
+    let mut rng = Rng(0xD1B54A32D192ED03);
+    for reversed in [false, true] {
+        for round in 0..200 {
+            let new = || {
+                if reversed {
+                    Glass::new_reversed()
+                } else {
+                    Glass::new()
+                }
+            };
+            let (mut buy_book, mut sell_book) = (new(), new());
+            let mut levels = BTreeMap::new();
+            // A few dense leaves, low and high in the price range.
+            for _ in 0..40 {
+                let base = [0u32, 64, 1 << 20, u32::MAX - 127][rng.below(4) as usize];
+                let k = base + rng.below(64) as u32;
+                let q = match rng.below(3) {
+                    0 => 1 + rng.below(1000),
+                    1 => (1 << 52) + rng.below(1 << 40),
+                    _ => u64::MAX / (1 + rng.below(8)),
+                };
+                levels.insert(k, q);
+            }
+            for (&k, &q) in &levels {
+                buy_book.insert(k, q);
+                sell_book.insert(k, q);
+            }
+            let shares = match round % 4 {
+                0 => 1 + rng.below(100),
+                1 => rng.next(),
+                2 => u64::MAX,
+                _ => 1 << (40 + rng.below(24)),
+            };
+            let ctx = format!("round {round}, shares {shares}, reversed={reversed}");
+
+            let buy_order: Vec<(u32, u64)> = buy_book.iter().collect();
+            let (cost, left) = oracle_fill_u128(&buy_order, shares);
+            assert_eq!(
+                buy_book.compute_buy_cost(shares),
+                cost,
+                "compute_buy_cost ({ctx})"
+            );
+            assert_eq!(buy_book.buy_shares(shares), cost, "buy_shares ({ctx})");
+            assert_eq!(
+                buy_book.iter().collect::<Vec<_>>(),
+                left,
+                "after buy ({ctx})"
+            );
+
+            let mut sell_order: Vec<(u32, u64)> = sell_book.iter().collect();
+            sell_order.reverse();
+            let (proceeds, mut left) = oracle_fill_u128(&sell_order, shares);
+            left.reverse();
+            assert_eq!(
+                sell_book.compute_sell_cost(shares),
+                proceeds,
+                "compute_sell_cost ({ctx})"
+            );
+            assert_eq!(
+                sell_book.sell_shares(shares),
+                proceeds,
+                "sell_shares ({ctx})"
+            );
+            assert_eq!(
+                sell_book.iter().collect::<Vec<_>>(),
+                left,
+                "after sell ({ctx})"
+            );
+        }
+    }
+}
+
+/// The overflow-tier hasher is pluggable and invisible: a spill-heavy
+/// sequence gives identical results under SipHash and a fixed-seed ahash.
+#[test]
+fn custom_hasher_matches_default() {
+    use std::hash::{BuildHasherDefault, DefaultHasher};
+
+    let mut rng = Rng(0x94D049BB133111EB);
+    let mut default = Glass::new();
+    let mut sip: Glass<u64, _> = Glass::with_hasher(BuildHasherDefault::<DefaultHasher>::default());
+    let mut seeded: Glass<u64, _> =
+        Glass::with_hasher_reversed(glass_rs::DefaultHashBuilder::with_seeds(1, 2, 3, 4));
+    let mut oracle = BTreeMap::new();
+    for step in 0..30_000 {
+        let k = rng.below(20_000) as u32;
+        match rng.below(16) {
+            0..=9 => {
+                let v = 1 + rng.below(100);
+                default.insert(k, v);
+                sip.insert(k, v);
+                seeded.insert(k, v);
+                oracle.insert(k, v);
+            }
+            10..=13 => {
+                let expected = oracle.remove(&k);
+                assert_eq!(default.remove(k), expected, "step {step}");
+                assert_eq!(sip.remove(k), expected, "step {step}");
+                assert_eq!(seeded.remove(k), expected, "step {step}");
+            }
+            _ => {
+                let shares = rng.below(200);
+                let cost = default.buy_shares(shares);
+                assert_eq!(sip.buy_shares(shares), cost, "step {step}");
+                // Reversed: selling from the back consumes the lowest prices.
+                assert_eq!(seeded.sell_shares(shares), cost, "step {step}");
+                assert_eq!(oracle_buy_shares(&mut oracle, shares), cost, "step {step}");
+            }
+        }
+    }
+    assert!(default.len() > 4096, "test should spill");
+    let ascending: Vec<_> = default.iter().collect();
+    assert_eq!(sip.iter().collect::<Vec<_>>(), ascending);
+    let mut descending: Vec<_> = seeded.iter().collect();
+    descending.reverse();
+    assert_eq!(descending, ascending);
+    let upper = sip.split_off(10_000);
+    assert_eq!(sip.len() + upper.len(), ascending.len());
+}
+
+/// cancel_partial below, at and above the resting size, in both tiers.
+#[test]
+fn cancel_partial_caps_at_resting_quantity() {
+    let mut glass = Glass::new();
+    let mut oracle = BTreeMap::new();
+    for k in 0..5000u32 {
+        glass.insert(k * 2, 100);
+        oracle.insert(k * 2, 100);
+    }
+    let universe: Vec<u32> = (0..10_002).collect();
+    // 10 lives in the trie, 9000 in the overflow tier.
+    for k in [10u32, 9000] {
+        assert_eq!(glass.cancel_partial(k, 30), 30, "less ({k})");
+        assert_eq!(glass.get(k), Some(70));
+        assert_eq!(glass.cancel_partial(k, 70), 70, "equal ({k})");
+        assert_eq!(glass.get(k), None);
+        assert_eq!(glass.cancel_partial(k, 5), 0, "absent ({k})");
+        oracle.remove(&k);
+    }
+    assert_eq!(glass.cancel_partial(12, u64::MAX), 100, "more");
+    oracle.remove(&12);
+    assert_eq!(glass.cancel_partial(11, 1), 0, "never present");
+    assert_eq!(glass.glass_size(), 4096, "trie refilled after cancels");
+    check_all(&glass, &oracle, &universe, "after cancels");
+
+    let mut compact = GlassU32::new_compact();
+    compact.insert(7, u32::MAX);
+    assert_eq!(compact.cancel_partial(7, u64::MAX), u32::MAX as u64);
+    assert!(compact.is_empty());
+}
+
+/// OrderBook signals against hand-computed values, and None on a one-sided
+/// book.
+#[test]
+fn order_book_midpoint_and_microprice() {
+    let mut book = OrderBook::new();
+    assert_eq!(book.midpoint(), None);
+    assert_eq!(book.microprice(), None);
+    book.bids.insert(100, 3);
+    book.bids.insert(98, 50); // deeper bid: must not count
+    assert_eq!(book.midpoint(), None, "asks empty");
+    book.asks.insert(104, 1);
+    book.asks.insert(110, 50);
+    assert_eq!(book.midpoint(), Some(102.0));
+    // (100 * 1 + 104 * 3) / (3 + 1)
+    assert_eq!(book.microprice(), Some(103.0));
+
+    book.asks.buy_shares(1); // best ask is now 110 x 50
+    assert_eq!(book.midpoint(), Some(105.0));
+    // (100 * 50 + 110 * 3) / 53
+    assert_eq!(book.microprice(), Some(5330.0 / 53.0));
+    book.bids.clear();
+    assert_eq!(book.microprice(), None, "bids empty");
+}
+
+/// Every FrozenGlass read agrees with the glass it was frozen from, on a
+/// spilled book in both orientations.
+#[test]
+fn frozen_glass_matches_source() {
+    let mut rng = Rng(0xBF58476D1CE4E5B9);
+    for reversed in [false, true] {
+        let new = || {
+            if reversed {
+                Glass::new_reversed()
+            } else {
+                Glass::new()
+            }
+        };
+        let (mut glass, mut source) = (new(), new());
+        for _ in 0..9000 {
+            let k = rng.below(30_000) as u32;
+            let v = 1 + rng.below(1000);
+            glass.insert(k, v);
+            source.insert(k, v);
+        }
+        glass.insert(u32::MAX, 3);
+        source.insert(u32::MAX, 3);
+        let frozen = glass.freeze();
+        let ctx = format!("reversed={reversed}");
+
+        assert_eq!(frozen.len(), source.len(), "len ({ctx})");
+        assert_eq!(frozen.is_reversed(), reversed);
+        assert_eq!(frozen.min(), source.min(), "min ({ctx})");
+        assert_eq!(frozen.max(), source.max(), "max ({ctx})");
+        assert_eq!(
+            frozen.iter().collect::<Vec<_>>(),
+            source.iter().collect::<Vec<_>>()
+        );
+        for k in (0..30_010).chain([u32::MAX - 1, u32::MAX]) {
+            assert_eq!(frozen.get(k), source.get(k), "get({k}) ({ctx})");
+        }
+        for i in (0..source.len() + 2).step_by(7) {
+            assert_eq!(frozen.nth(i), source.nth(i), "nth({i}) ({ctx})");
+        }
+        for _ in 0..300 {
+            let a = rng.below(31_000) as u32;
+            let b = rng.below(31_000) as u32;
+            let mine = frozen.range(a..=b).to_vec();
+            assert_eq!(
+                mine,
+                source.range(a..=b).collect::<Vec<_>>(),
+                "{a}..={b} ({ctx})"
+            );
+            let mine = frozen.range(a..b).to_vec();
+            assert_eq!(
+                mine,
+                source.range(a..b).collect::<Vec<_>>(),
+                "{a}..{b} ({ctx})"
+            );
+            let mine = frozen.range(a..).to_vec();
+            assert_eq!(mine, source.range(a..).collect::<Vec<_>>(), "{a}.. ({ctx})");
+        }
+        for shares in [0, 1, 999, 50_000, 2_000_000, u64::MAX] {
+            assert_eq!(
+                frozen.compute_buy_cost(shares),
+                source.compute_buy_cost(shares),
+                "compute_buy_cost({shares}) ({ctx})"
+            );
+        }
+    }
+    assert!(Glass::new().freeze().is_empty());
+    assert_eq!(Glass::new().freeze().get(0), None);
+}
+
+/// remove_all leaves the same book as one remove per key, with the right
+/// count, including absent and duplicate keys across both tiers.
+#[test]
+fn remove_all_matches_individual_removes() {
+    let mut rng = Rng(0x3C6EF372FE94F82B);
+    for reversed in [false, true] {
+        let new = || {
+            if reversed {
+                Glass::new_reversed()
+            } else {
+                Glass::new()
+            }
+        };
+        let (mut batched, mut looped) = (new(), new());
+        for _ in 0..7000 {
+            let k = rng.below(12_000) as u32;
+            let v = 1 + rng.below(100);
+            batched.insert(k, v);
+            looped.insert(k, v);
+        }
+        for round in 0..20 {
+            let keys: Vec<u32> = (0..rng.below(800))
+                .map(|_| rng.below(12_500) as u32)
+                .collect();
+            let expected = keys.iter().filter(|&&k| looped.remove(k).is_some()).count();
+            assert_eq!(batched.remove_all(&keys).0, expected, "round {round}");
+            assert!(batched == looped, "round {round}, reversed={reversed}");
+            assert_eq!(batched.glass_size(), looped.glass_size(), "round {round}");
+            assert_eq!(batched.min(), looped.min());
+            assert_eq!(batched.max(), looped.max());
+        }
+    }
+}
+
+/// count_in_range equals counting range() output, across tiers, leaf
+/// boundaries and both orientations.
+#[test]
+fn count_in_range_matches_iteration() {
+    let mut rng = Rng(0xA54FF53A5F1D36F1);
+    for reversed in [false, true] {
+        let mut glass = if reversed {
+            Glass::new_reversed()
+        } else {
+            Glass::new()
+        };
+        for _ in 0..7000 {
+            glass.insert(rng.below(15_000) as u32, 1 + rng.below(9));
+        }
+        glass.insert(u32::MAX, 1);
+        glass.insert(0, 1);
+        for _ in 0..2000 {
+            let (a, b) = match rng.below(4) {
+                0 => (
+                    (rng.below(240) * 64) as u32,
+                    (rng.below(240) * 64 + 63) as u32,
+                ),
+                1 => (0, u32::MAX),
+                _ => (rng.below(16_000) as u32, rng.below(16_000) as u32),
+            };
+            let (lo, hi) = if reversed {
+                (a.max(b), a.min(b))
+            } else {
+                (a.min(b), a.max(b))
+            };
+            assert_eq!(
+                glass.count_in_range(lo, hi),
+                glass.range(lo..=hi).count(),
+                "count_in_range({lo}, {hi}), reversed={reversed}"
+            );
+            // Inverted bounds are empty.
+            if lo != hi {
+                assert_eq!(glass.count_in_range(hi, lo), 0);
+            }
+        }
+        let (first, last) = (glass.min().unwrap().0, glass.max().unwrap().0);
+        assert_eq!(glass.count_in_range(first, last), glass.len());
+    }
+}
+
+/// iter() reports an exact length that counts down as it is consumed,
+/// across both tiers; range() reports a sound upper bound.
+#[test]
+fn iter_len_is_exact() {
+    let mut glass = Glass::new();
+    assert_eq!(glass.iter().len(), 0);
+    for k in 0..5000u32 {
+        glass.insert(k * 3, 1);
+    }
+    let mut it = glass.iter();
+    assert_eq!(it.len(), glass.len());
+    for consumed in 1..=glass.len() {
+        assert!(it.next().is_some());
+        assert_eq!(it.len(), glass.len() - consumed, "after {consumed}");
+    }
+    assert_eq!(it.next(), None);
+    assert_eq!(it.len(), 0);
+
+    let (lo, hi) = glass.range(100..200).size_hint();
+    let n = glass.range(100..200).count();
+    assert!(lo <= n && hi.is_some_and(|hi| n <= hi));
+
+    let mut owned = glass.into_iter();
+    assert_eq!(owned.len(), 5000);
+    owned.next();
+    assert_eq!(owned.len(), 4999);
+}
+
+/// decay_all scales every level across both tiers, dropping those that
+/// round to zero, and saturates on growth.
+#[test]
+fn decay_all_scales_and_drops_zeros() {
+    let mut rng = Rng(0x510E527FADE682D1);
+    let mut glass = Glass::new();
+    let mut oracle = BTreeMap::new();
+    for _ in 0..7000 {
+        let k = rng.below(12_000) as u32;
+        let v = 1 + rng.below(6);
+        glass.insert(k, v);
+        oracle.insert(k, v);
+    }
+    let universe: Vec<u32> = (0..12_100).collect();
+
+    glass.decay_all(1, 2);
+    oracle = oracle
+        .into_iter()
+        .filter_map(|(k, v)| (v / 2 > 0).then_some((k, v / 2)))
+        .collect();
+    check_all(&glass, &oracle, &universe, "after 1/2 decay");
+    assert!(glass.iter().all(|(_, v)| v >= 1));
+
+    glass.decay_all(9, 10);
+    oracle = oracle
+        .into_iter()
+        .filter_map(|(k, v)| (v * 9 / 10 > 0).then_some((k, v * 9 / 10)))
+        .collect();
+    check_all(&glass, &oracle, &universe, "after 9/10 decay");
+
+    glass.decay_all(3, 3);
+    check_all(&glass, &oracle, &universe, "identity");
+
+    let mut big = Glass::new();
+    big.insert(5, u64::MAX / 2 + 1);
+    big.insert(6, 4);
+    big.decay_all(3, 1);
+    assert_eq!(big.get(5), Some(u64::MAX), "growth saturates");
+    assert_eq!(big.get(6), Some(12));
+    big.decay_all(0, 1);
+    assert!(big.is_empty());
+}
+
+/// insert_or_max keeps the larger value in both tiers and inserts absent
+/// levels with the usual tier routing.
+#[test]
+fn insert_or_max_keeps_larger() {
+    let mut rng = Rng(0x9B05688C2B3E6C1F);
+    let mut glass = Glass::new();
+    let mut oracle = BTreeMap::new();
+    for _ in 0..20_000 {
+        let k = rng.below(9000) as u32;
+        let v = rng.below(100);
+        glass.insert_or_max(k, v);
+        if v > 0 {
+            let e = oracle.entry(k).or_insert(v);
+            *e = (*e).max(v);
+        }
+    }
+    assert!(glass.len() > 4096, "test should spill");
+    let universe: Vec<u32> = (0..9100).collect();
+    check_all(&glass, &oracle, &universe, "after merges");
+
+    // Trie level and overflow level: smaller is a no-op, larger overwrites.
+    let (low, high) = (oracle.keys().next().copied().unwrap(), 8999);
+    for k in [low, high] {
+        glass.insert(k, 50);
+        glass.insert_or_max(k, 10);
+        assert_eq!(glass.get(k), Some(50), "smaller ({k})");
+        glass.insert_or_max(k, 80);
+        assert_eq!(glass.get(k), Some(80), "larger ({k})");
+        glass.insert_or_max(k, 0);
+        assert_eq!(glass.get(k), Some(80), "zero ({k})");
+    }
+}
+
+/// insert_if_absent only ever adds levels: present levels in either tier
+/// keep their value, and 0 is never stored.
+#[test]
+fn insert_if_absent_keeps_existing() {
+    let mut rng = Rng(0x3C6EF372FE94F82B);
+    let mut glass = Glass::new();
+    let mut oracle = BTreeMap::new();
+    for _ in 0..20_000 {
+        let k = rng.below(9000) as u32;
+        let v = rng.below(100);
+        let inserted = v > 0 && !oracle.contains_key(&k);
+        if inserted {
+            oracle.insert(k, v);
+        }
+        assert_eq!(glass.insert_if_absent(k, v), inserted, "key {k}");
+    }
+    assert!(glass.len() > 4096, "test should spill");
+    let universe: Vec<u32> = (0..9100).collect();
+    check_all(&glass, &oracle, &universe, "after inserts");
+
+    // Trie level and overflow level both stay put; a fresh one goes in.
+    let (low, high) = (glass.min().unwrap().0, glass.max().unwrap().0);
+    for k in [low, high] {
+        assert!(!glass.insert_if_absent(k, 1), "present ({k})");
+        assert_eq!(glass.get(k), oracle.get(&k).copied());
+    }
+    assert_eq!(glass.location(high), Some(Location::Preempt));
+    let fresh = (0..9000).find(|k| !oracle.contains_key(k)).unwrap();
+    assert!(glass.insert_if_absent(fresh, 5));
+    assert_eq!(glass.get(fresh), Some(5));
+}
+
+/// FromIterator/Extend round-trip through iter().
+#[test]
+fn from_iterator_round_trip() {
+    let src: Vec<(u32, u64)> = (0..1000u32).map(|i| (i * 5, i as u64 + 1)).collect();
+    let glass: Glass = src.iter().copied().collect();
+    assert_eq!(glass.len(), 1000);
+    let collected: Vec<(u32, u64)> = glass.iter().collect();
+    assert_eq!(collected, src);
+}
+
+/// insert_many_unsorted must leave exactly the state of a plain insert loop:
+/// last-wins duplicates, zero deletes, and spill across the 4096 boundary.
+#[test]
+fn insert_many_unsorted_matches_insert_loop() {
+    let mut rng = Rng(0xD1B54A32D192ED03);
+    let mut looped = Glass::new();
+    let mut batched = Glass::new();
+    let mut oracle = BTreeMap::new();
+
+    for round in 0..4 {
+        let batch: Vec<(u32, u64)> = (0..3000)
+            .map(|_| {
+                let key = (rng.below(6000) * 3) as u32;
+                let value = if rng.below(10) == 0 {
+                    0
+                } else {
+                    rng.below(500) + 1
+                };
+                (key, value)
+            })
+            .collect();
+        for &(k, v) in &batch {
+            looped.insert(k, v);
+            if v == 0 {
+                oracle.remove(&k);
+            } else {
+                oracle.insert(k, v);
+            }
+        }
+        batched.insert_many_unsorted(&batch);
+
+        let expected: Vec<(u32, u64)> = looped.iter().collect();
+        assert_eq!(
+            batched.iter().collect::<Vec<_>>(),
+            expected,
+            "round {round}"
+        );
+        let keys: Vec<u32> = oracle.keys().copied().collect();
+        check_all(&batched, &oracle, &keys, "insert_many_unsorted");
+    }
+    assert_eq!(batched.glass_size(), looped.glass_size());
+    assert_eq!(
+        batched.buy_shares(u64::MAX),
+        oracle_buy_shares(&mut oracle, u64::MAX)
+    );
+}
+
+/// A reversed glass must behave exactly like `BTreeMap<Reverse<u32>, u64>`:
+/// descending order everywhere, with buys sweeping from the highest price.
+/// The key universe crosses the 4096-level boundary (so the trie holds the
+/// highest prices) and includes both ends of the `u32` range.
+#[test]
+fn reversed_glass_matches_descending_btreemap() {
+    let mut rng = Rng(0xA0761D6478BD642F);
+    let mut glass = Glass::new_reversed();
+    let mut oracle: BTreeMap<u32, u64> = BTreeMap::new();
+    assert!(glass.is_reversed());
+
+    let key_for = |r: u64| -> u32 {
+        match r % 10 {
+            0 => [0, 1, u32::MAX - 1, u32::MAX][(r / 10 % 4) as usize],
+            1..=6 => 50_000 + (r / 10 % 3000) as u32,
+            _ => ((r / 10) % 8000) as u32 * 16,
+        }
+    };
+
+    for step in 0..100_000u64 {
+        let r = rng.next();
+        let key = key_for(r);
+        match r % 20 {
+            0..=8 => {
+                let v = rng.below(1000) + 1;
+                glass.insert(key, v);
+                oracle.insert(key, v);
+            }
+            9..=12 => {
+                assert_eq!(glass.remove(key), oracle.remove(&key), "remove at {step}");
+            }
+            13 => {
+                assert_eq!(glass.min(), oracle_max(&oracle), "min at {step}");
+                assert_eq!(glass.max(), oracle_min(&oracle), "max at {step}");
+            }
+            14 => {
+                let target = rng.below(5000);
+                assert_eq!(
+                    glass.compute_buy_cost(target),
+                    oracle_sell_cost(&oracle, target),
+                    "compute_buy_cost at {step}"
+                );
+                assert_eq!(
+                    glass.compute_sell_cost(target),
+                    oracle_buy_cost(&oracle, target),
+                    "compute_sell_cost at {step}"
+                );
+            }
+            15 => {
+                let shares = rng.below(3000);
+                assert_eq!(
+                    glass.buy_shares(shares),
+                    oracle_sell_shares(&mut oracle, shares),
+                    "buy_shares at {step}"
+                );
+            }
+            16 => {
+                let shares = rng.below(3000);
+                assert_eq!(
+                    glass.sell_shares(shares),
+                    oracle_buy_shares(&mut oracle, shares),
+                    "sell_shares at {step}"
+                );
+            }
+            17 => {
+                assert_eq!(
+                    glass.next_level(key),
+                    oracle.range(..key).next_back().map(|(&k, &v)| (k, v)),
+                    "next_level({key}) at {step}"
+                );
+                use std::ops::Bound::{Excluded, Unbounded};
+                assert_eq!(
+                    glass.prev_level(key),
+                    oracle
+                        .range((Excluded(key), Unbounded))
+                        .next()
+                        .map(|(&k, &v)| (k, v)),
+                    "prev_level({key}) at {step}"
+                );
+            }
+            18 => {
+                let n = oracle.len();
+                if n > 0 {
+                    let k = rng.below(n as u64) as usize;
+                    let expected = oracle.keys().rev().nth(k).copied().map(|key| {
+                        let v = oracle.remove(&key).unwrap();
+                        (key, v)
+                    });
+                    assert_eq!(
+                        glass.remove_by_index(k),
+                        expected,
+                        "remove_by_index at {step}"
+                    );
+                }
+            }
+            _ => {
+                // Reversed bounds: `range(hi..lo)` walks down from hi.
+                let lo = key.saturating_sub(rng.below(4000) as u32);
+                if lo < key {
+                    let mine: Vec<(u32, u64)> = glass.range(key..lo).take(50).collect();
+                    let theirs: Vec<(u32, u64)> = oracle
+                        .range(lo + 1..=key)
+                        .rev()
+                        .take(50)
+                        .map(|(&k, &v)| (k, v))
+                        .collect();
+                    assert_eq!(mine, theirs, "range({key}..{lo}) at {step}");
+                }
+                let depth = (rng.below(60) + 1) as usize;
+                let mut buf = Vec::new();
+                glass.top_levels(depth, &mut buf);
+                let expected: Vec<(u32, u64)> = oracle
+                    .iter()
+                    .rev()
+                    .take(depth)
+                    .map(|(&k, &v)| (k, v))
+                    .collect();
+                assert_eq!(buf, expected, "top_levels({depth}) at {step}");
+            }
+        }
+    }
+
+    let mine: Vec<(u32, u64)> = glass.iter().collect();
+    let theirs: Vec<(u32, u64)> = oracle.iter().rev().map(|(&k, &v)| (k, v)).collect();
+    assert_eq!(mine, theirs, "final iter");
+    for (&k, &v) in &oracle {
+        assert_eq!(glass.get(k), Some(v), "final get({k})");
+    }
+    let upper = glass.split_off(60_000);
+    assert!(upper.is_reversed());
+    assert!(upper.iter().all(|(k, _)| k <= 60_000));
+    assert!(glass.iter().all(|(k, _)| k > 60_000));
+    assert_eq!(glass.len() + upper.len(), oracle.len());
+
+    let mut rebuilt = Glass::new_reversed();
+    let entries: Vec<(u32, u64)> = oracle.iter().map(|(&k, &v)| (k, v)).collect();
+    rebuilt.insert_many_unsorted(&entries);
+    assert_eq!(rebuilt.iter().collect::<Vec<_>>(), theirs, "bulk load");
+    for t in [10_000u64, 300_000, 10_000_000, u64::MAX] {
+        assert_eq!(
+            rebuilt.compute_buy_cost(t),
+            oracle_sell_cost(&oracle, t),
+            "compute_buy_cost({t}) deep sweep"
+        );
+    }
+    assert_eq!(
+        rebuilt.buy_shares(u64::MAX),
+        oracle_sell_shares(&mut oracle, u64::MAX),
+        "full drain"
+    );
+    assert!(rebuilt.is_empty());
+}
+
+/// The u32-quantity glass runs the same mixed workload as the u64 one
+/// (tiers, chain overflow, sweeps), with quantities up to `u32::MAX` so cost
+/// accumulation must widen to u64.
+#[test]
+fn compact_u32_matches_btreemap() {
+    let mut rng = Rng(0xBF58476D1CE4E5B9);
+    let mut glass = GlassU32::new_compact();
+    let mut oracle: BTreeMap<u32, u64> = BTreeMap::new();
+    let widen = |v: Option<(u32, u32)>| v.map(|(k, q)| (k, q as u64));
+
+    let key_for = |r: u64| -> u32 {
+        match r % 10 {
+            0..=5 => 1000 + (r / 10 % 3000) as u32,
+            6..=8 => ((r / 10) % 8000) as u32 * 16,
+            _ => (((r / 10) % 32) as u32) << 18,
+        }
+    };
+    let qty_for = |r: u64| -> u32 {
+        if r.is_multiple_of(50) {
+            u32::MAX - (r % 7) as u32
+        } else {
+            (r % 1000) as u32 + 1
+        }
+    };
+
+    for step in 0..100_000u64 {
+        let r = rng.next();
+        let key = key_for(r);
+        match r % 20 {
+            0..=7 => {
+                let v = qty_for(rng.next());
+                glass.insert(key, v);
+                oracle.insert(key, v as u64);
+            }
+            8..=10 => {
+                assert_eq!(
+                    glass.remove(key).map(u64::from),
+                    oracle.remove(&key),
+                    "remove({key}) at {step}"
+                );
+            }
+            11..=12 => {
+                assert_eq!(
+                    glass.get(key).map(u64::from),
+                    oracle.get(&key).copied(),
+                    "get({key}) at {step}"
+                );
+            }
+            13 => {
+                let updated = glass.update_value(key, |v| *v = v.saturating_sub(2));
+                if updated {
+                    let q = oracle.get_mut(&key).unwrap();
+                    *q = q.saturating_sub(2);
+                    if *q == 0 {
+                        oracle.remove(&key);
+                    }
+                } else {
+                    assert!(!oracle.contains_key(&key), "update_value missed {key}");
+                }
+            }
+            14 => {
+                assert_eq!(widen(glass.min()), oracle_min(&oracle), "min at {step}");
+                assert_eq!(widen(glass.max()), oracle_max(&oracle), "max at {step}");
+            }
+            15 => {
+                let target = rng.below(1 << 34);
+                assert_eq!(
+                    glass.compute_buy_cost(target),
+                    oracle_buy_cost(&oracle, target),
+                    "compute_buy_cost({target}) at {step}"
+                );
+                assert_eq!(
+                    glass.compute_sell_cost(target),
+                    oracle_sell_cost(&oracle, target),
+                    "compute_sell_cost({target}) at {step}"
+                );
+            }
+            16 => {
+                let shares = rng.below(3000);
+                assert_eq!(
+                    glass.buy_shares(shares),
+                    oracle_buy_shares(&mut oracle, shares),
+                    "buy_shares({shares}) at {step}"
+                );
+            }
+            17 => {
+                let shares = rng.below(3000);
+                assert_eq!(
+                    glass.sell_shares(shares),
+                    oracle_sell_shares(&mut oracle, shares),
+                    "sell_shares({shares}) at {step}"
+                );
+            }
+            18 => {
+                let n = oracle.len();
+                if n > 0 {
+                    let k = rng.below(n as u64) as usize;
+                    let expected = oracle.keys().nth(k).copied().map(|key| {
+                        let v = oracle.remove(&key).unwrap();
+                        (key, v)
+                    });
+                    assert_eq!(
+                        widen(glass.remove_by_index(k)),
+                        expected,
+                        "remove_by_index({k}) at {step}"
+                    );
+                }
+            }
+            _ => {
+                let depth = (rng.below(200) + 1) as usize;
+                let mut buf = Vec::new();
+                glass.top_levels(depth, &mut buf);
+                let mine: Vec<(u32, u64)> = buf.iter().map(|&(k, q)| (k, q as u64)).collect();
+                let expected: Vec<(u32, u64)> =
+                    oracle.iter().take(depth).map(|(&k, &v)| (k, v)).collect();
+                assert_eq!(mine, expected, "top_levels({depth}) at {step}");
+            }
+        }
+    }
+
+    let mine: Vec<(u32, u64)> = glass.iter().map(|(k, q)| (k, q as u64)).collect();
+    let theirs: Vec<(u32, u64)> = oracle.iter().map(|(&k, &v)| (k, v)).collect();
+    assert_eq!(mine, theirs, "final iter");
+    for t in [10_000u64, 300_000, 10_000_000, 1 << 40, u64::MAX] {
+        assert_eq!(
+            glass.compute_buy_cost(t),
+            oracle_buy_cost(&oracle, t),
+            "compute_buy_cost({t}) deep sweep"
+        );
+    }
+    assert_eq!(
+        glass.buy_shares(u64::MAX),
+        oracle_buy_shares(&mut oracle, u64::MAX),
+        "full drain"
+    );
+    assert!(glass.is_empty());
 }
 
 /// Randomized differential test crossing the preemption boundary (> 4096 live